@@ -152,6 +152,41 @@ fn nope() {
     let _nope = NoDefault { x: 10 };
 }
 ```
+With `checked`, `autodefault` additionally asserts that every type it fills
+implements `Default`, so that the error clearly states which type is missing
+the implementation. Like the errors from `require_field`, it's reported at
+the struct expression itself: the first one of each type in each function.
+The type is inferred from the struct expression, so this works for generic
+types too, even when they're written without their generic arguments (like
+`Grid { width: 3 }`). With `typed`, the type is named explicitly instead:
+
+```compile_fail
+use autodefault::autodefault;
+
+struct NoDefault {
+    x: i32,
+    y: i32,
+}
+
+#[autodefault(checked)]
+fn nope() {
+    // error[E0277]: the trait bound `NoDefault: Default` is not satisfied
+    let _nope = NoDefault { x: 10 };
+}
+```
+
+The rewrite itself is minimal and deterministic, so that expanded output (for
+instance, from `cargo expand`) is stable: the `..rest` is appended after the
+existing fields, and a single `,` is added first only if the fields don't
+already end with one. That comma can't be omitted, since `Foo { x: 10 ..rest }`
+would parse `10..rest` as a range. Nothing else about the struct expression is
+changed; `Foo { x: 10 }` always becomes exactly
+`Foo { x: 10, ..::core::default::Default::default() }`.
+The struct expression stays where it was, so wherever it appears (a function
+or method argument, an index, a closure body, and so on), everything is
+evaluated in the same order as before, with the `..rest` evaluated after the
+struct's explicit fields.
+
 # Filtering `Default` insertions
 
 If you only want to add `..Default::default()`  to some of the structs in your
@@ -187,338 +222,8294 @@ fn example2() {
 }
 ```
 
-# Other behaviors
+Names are matched against the last component of the struct's path, so
+`only(HasDefault)` matches both `HasDefault {}` and `types::HasDefault {}`.
+Generic arguments are ignored, so `only(Grid)` also matches
+`Grid::<u8> { width: 3 }`. If generic struct expressions shouldn't be filled
+at all (for instance, because a type parameter might not implement
+`Default`), use `skip_generics`, which skips every struct expression with
+generic arguments, like `Wrapper::<T> { value }`.
+A struct expression that names one of the function's own type parameters
+(or those of its `impl` block), like `T { .. }`, is never filled, since
+filling it would only make the resulting error more confusing. With `warn`
+on nightly, these are pointed out with a warning.
 
-`autodefault` will not descend into nested item definitions; if you nest an
-`fn` item inside another `fn`, you'll need to tag the inner function with
-`autodefault` again.
+Because only the last component is checked, a filter can also match an enum
+variant that happens to share a name with a struct, like `Shape::Rect { .. }`
+alongside `struct Rect`. Variants can't be built with `..Default::default()`,
+so this is always a compile error. `strict_structs` avoids it by only filling
+paths that look like plain structs: a single name, or a path where every
+component before the last starts with a lowercase letter (a module path, like
+`geometry::Rect`). Anything like `Shape::Rect` or `Self::Rect`, where the
+component before the last looks like a type, is left alone.
+
+You can also match by path prefix, using a trailing `*`. This is especially
+useful for matching everything from a particular crate, like `only(bevy::*)`:
 
 ```
 use autodefault::autodefault;
 
-#[derive(Default)]
-struct HasDefault {
-    x: i32
+mod config {
+    #[derive(Default)]
+    pub struct Window {
+        pub width: u32,
+        pub height: u32,
+    }
 }
 
-struct NoDefault {
-    x: i32
+struct App {
+    window: config::Window,
 }
 
-#[autodefault]
-fn outer() {
-    let _x = HasDefault {};
-
-    fn inner() {
-        let _x = NoDefault {x: 10};
-    }
+#[autodefault(only(config::*))]
+fn example() {
+    let _app = App { window: config::Window { width: 800 } };
 }
 ```
 
-*/
+A full path (without the `*`) matches only struct expressions written with
+exactly that path. Any segment can be `_`, which matches any single segment;
+for instance, `only(proto::_::Message)` matches `proto::v1::Message` and
+`proto::v2::Message`, but not `proto::Message` or `Message`. `_` works in
+prefixes too, like `only(proto::_::*)`. A leading `::` is ignored, on both the
+filter and the struct expression, so `only(a::Foo)` matches `::a::Foo { .. }`.
 
-use std::collections::HashSet;
+As a coarser filter, `segments = N` matches every struct expression whose
+path has exactly `N` components. For instance, if generated code always
+refers to its types as `package::Message` and your own types are referred to
+by name, `only(segments = 2)` fills only the generated ones.
 
-use proc_macro::TokenStream;
-use proc_macro2::TokenStream as TokenStream2;
-use quote::ToTokens;
-use syn::{
-    parenthesized,
-    parse::Parse,
-    parse2, parse_quote,
-    punctuated::Punctuated,
-    visit_mut::{visit_expr_struct_mut, VisitMut},
-    ExprStruct, Ident, Item, Token,
-};
+If the list is easier to produce as strings (for instance, because it's
+generated), `only_from` and `except_from` take an array of string literals,
+each of which is parsed like an entry in `only` or `except`:
+`only_from(["Window", "bevy::*"])`. These can be combined with `only` and
+`except`, respectively, and all of the entries are used together.
 
-#[derive(Debug)]
-enum Rule {
-    Only,
-    Except,
+`only` and `except` can't be given together. For more complex rules, use
+`rules(...)` with a list of `only(...)` and `except(...)` clauses (or their
+`_from` forms), which are evaluated in order:
+
+- The last clause that matches a struct expression decides whether it's
+  filled: `only` fills it, and `except` doesn't.
+- If no clause matches, the first clause decides: a list starting with
+  `except` fills it, and one starting with `only` doesn't.
+
+So `rules(only(X))` is the same as `only(X)`, and `rules(except(X))` is the
+same as `except(X)`. For instance, this fills everything in `ui`, except for
+`ui::RawHandle`, plus `net::Socket`:
+
+```
+use autodefault::autodefault;
+
+mod ui {
+    #[derive(Default)]
+    pub struct Button { pub label: &'static str, pub width: u32 }
+    pub struct RawHandle { pub id: u32 }
 }
 
-#[derive(Debug)]
-enum Rules {
-    All,
-    Only(HashSet<Ident>),
-    Except(HashSet<Ident>),
+mod net {
+    #[derive(Default)]
+    pub struct Socket { pub port: u16, pub timeout: u32 }
+    pub struct Packet { pub len: usize }
 }
 
-impl Parse for Rules {
-    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        if input.is_empty() {
-            return Ok(Rules::All);
-        }
+#[autodefault(rules(only(ui::*), except(ui::RawHandle), only(net::Socket)))]
+fn example() {
+    let _button = ui::Button { label: "OK" };
+    let _handle = ui::RawHandle { id: 3 };
+    let _socket = net::Socket { port: 80 };
+    let _packet = net::Packet { len: 0 };
+}
+```
 
-        let rule_ident: Ident = input.parse()?;
-        let rule = if rule_ident == "except" {
-            Rule::Except
-        } else if rule_ident == "only" {
-            Rule::Only
-        } else {
-            return Err(syn::Error::new(
-                rule_ident.span(),
-                "Expected 'except' or 'only'",
-            ));
-        };
+If the structs you're building are defined alongside the function, you can
+instead use `only_derive`, which will only add `..Default::default()` to
+structs that are defined locally and that `#[derive(Default)]`:
 
-        let content;
-        let _parens = parenthesized!(content in input);
+```
+use autodefault::autodefault;
 
-        let rules: Punctuated<Ident, Token![,]> = Punctuated::parse_terminated(&content)?;
-        let rules = rules.into_iter().collect();
+#[autodefault(only_derive)]
+fn example() {
+    #[derive(Default)]
+    struct HasDefault {
+        a: i32,
+        b: i32,
+    }
 
-        Ok(match rule {
-            Rule::Only => Rules::Only(rules),
-            Rule::Except => Rules::Except(rules),
-        })
+    struct NoDefault {
+        a: HasDefault,
     }
-}
 
-struct AutodefaultVisitor {
-    rules: Rules,
+    let _data = NoDefault { a: HasDefault { a: 10 } };
 }
+```
 
-impl AutodefaultVisitor {}
-
-impl VisitMut for AutodefaultVisitor {
-    fn visit_expr_struct_mut(&mut self, struct_expr: &mut ExprStruct) {
-        visit_expr_struct_mut(self, struct_expr);
+At the function level, this only considers structs defined directly in the
+function body; it can't see any other types, so it's best used with
+`#[autodefault]` applied to a whole module (see below).
 
-        let struct_ident = &struct_expr.path.segments.last().unwrap().ident;
+Sometimes a struct expression is written with a different name than the type
+it's ultimately bound to, such as with a type alias. With `use_binding_type`,
+struct expressions that directly initialize a `let` binding with a type
+annotation are matched against the annotation instead:
 
-        match &self.rules {
-            Rules::Only(allow_list) if !allow_list.contains(struct_ident) => return,
-            Rules::Except(deny_list) if deny_list.contains(struct_ident) => return,
-            _ => {}
-        }
+```
+use autodefault::autodefault;
 
-        // Add `..Default::default()` to structs that don't have a ..rest
-        // initializer
-        if struct_expr.dot2_token.is_none() && struct_expr.rest.is_none() {
-            // Make sure fields have trailing comma
-            if !struct_expr.fields.empty_or_trailing() {
-                struct_expr.fields.push_punct(parse_quote! {,});
-            }
+#[derive(Default)]
+struct HasDefault {
+    a: i32,
+    b: i32,
+}
 
-            // Add the ..Default::default()
-            struct_expr.dot2_token = Some(parse_quote! {..});
-            struct_expr.rest = Some(Box::new(parse_quote! {
-                ::core::default::Default::default()
-            }));
-        }
-    }
+type Alias = HasDefault;
 
-    fn visit_item_mut(&mut self, _: &mut Item) {}
+#[autodefault(only(HasDefault), use_binding_type)]
+fn example() {
+    let _data: HasDefault = Alias { a: 10 };
 }
+```
 
-fn autodefault_impl(attr: TokenStream2, item: TokenStream2) -> TokenStream2 {
-    let rules = match parse2(attr) {
-        Ok(rules) => rules,
-        Err(err) => return err.into_compile_error(),
-    };
+## Only filling returned values
 
-    let mut item = match parse2(item) {
-        Ok(item) => item,
-        Err(err) => return err.into_compile_error(),
-    };
+With `return_only`, `autodefault` will only fill struct expressions that are
+returned from the function, either as its final expression or in a `return`.
+The fields of a returned struct expression (and the elements of a returned
+tuple or array) count as returned too, as does each branch of a returned `if`
+or `match`. So do the arguments of a returned enum variant or tuple struct,
+like `Ok(Config { .. })` or `Some(Config { .. })`; these are recognized by
+their capitalized names. Everything else, like `let` bindings, the arguments
+of other functions and methods, conditions, scrutinees, and guards, is left
+alone:
 
-    let mut visitor = AutodefaultVisitor { rules };
+```
+use autodefault::autodefault;
 
-    visitor.visit_item_fn_mut(&mut item);
-    item.into_token_stream()
+#[derive(Default)]
+struct Config {
+    verbose: bool,
+    retries: u32,
 }
 
-/// Modify a function such that some or all struct expressions include
-/// `..Default::default()`.
-///
-/// See [module][crate] docs for details.
-#[proc_macro_attribute]
-pub fn autodefault(attr: TokenStream, item: TokenStream) -> TokenStream {
-    autodefault_impl(attr.into(), item.into()).into()
+struct Plain {
+    value: u32,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use proc_macro2::TokenStream as TokenStream2;
-    use quote::quote;
+fn count(plain: Plain) -> u32 {
+    plain.value
+}
 
-    #[test]
-    fn it_works() {
-        let input = quote! {
-            fn demo () {
-                let x = Foo {a: 10, b: 10};
-            }
-        };
-        let output: TokenStream2 = autodefault_impl(TokenStream2::new(), input);
+#[autodefault(return_only)]
+fn example(early: bool) -> Config {
+    let plain = Plain { value: 3 };
 
-        assert_eq!(
-            format!("{:?}", output),
-            format!(
-                "{:?}",
-                quote! {
-                    fn demo () {
-                        let x = Foo {a: 10, b: 10, ..::core::default::Default::default()};
-                    }
-                }
-            ),
-        )
+    if early {
+        return Config { verbose: true };
     }
 
-    #[test]
-    fn trailing_comma() {
-        let input = quote! {
-            fn demo () {
-                let x = Foo {a: 10, b: 10, };
-            }
-        };
-        let output: TokenStream2 = autodefault_impl(TokenStream2::new(), input);
+    Config { retries: count(Plain { value: plain.value }) }
+}
 
-        assert_eq!(
-            format!("{:?}", output),
-            format!(
-                "{:?}",
-                quote! {
-                    fn demo () {
-                        let x = Foo {a: 10, b: 10, ..::core::default::Default::default()};
-                    }
-                }
-            ),
-        )
+#[autodefault(return_only)]
+fn build(retries: u32) -> Result<Config, String> {
+    if retries > 10 {
+        return Err(format!("too many retries: {}", retries));
     }
 
-    #[test]
-    fn empty_struct() {
-        let input = quote! {
-            fn demo () {
-                let x = Foo {};
-            }
-        };
-        let output: TokenStream2 = autodefault_impl(TokenStream2::new(), input);
+    Ok(Config { retries })
+}
+```
 
-        assert_eq!(
-            format!("{:?}", output),
-            format!(
-                "{:?}",
-                quote! {
-                    fn demo () {
-                        let x = Foo {..::core::default::Default::default()};
-                    }
-                }
-            ),
-        )
-    }
+The bodies of closures and `async` blocks usually aren't considered to be in
+return position, since they aren't returned from the function itself. The
+exception is a function that returns an `impl Trait`, like an iterator built
+with `.map(|x| Config { retries: x })`; there, the closures and `async` blocks
+in the returned value are what produce the structs that are ultimately
+returned, so they count as returned too.
 
-    #[test]
-    fn existing_spread() {
-        let input = quote! {
-            fn demo () {
-                let x = Foo {a: 10, b: 10, ..foo()};
-            }
-        };
-        let output: TokenStream2 = autodefault_impl(TokenStream2::new(), input);
+## Only filling `let` bindings
 
-        assert_eq!(
-            format!("{:?}", output),
-            format!(
-                "{:?}",
-                quote! {
-                    fn demo () {
-                        let x = Foo {a: 10, b: 10, ..foo()};
-                    }
-                }
-            ),
-        )
-    }
+`bindings_only` is the opposite: it only fills struct expressions that are
+assigned directly to a `let` binding, for the common pattern of building a
+value and then tweaking it. Struct expressions used as arguments, returned,
+or nested inside other expressions are left alone:
 
-    #[test]
-    fn except() {
-        let output = autodefault_impl(
-            quote! { except(Ignore1, Ignore2) },
-            quote! {
-                fn demo() {
-                    let a = Ignore1 {};
-                    let b = Ignore2 {};
-                    let c = Default1 {};
-                    let d = Default2 {};
-                }
-            },
-        );
+```
+use autodefault::autodefault;
 
-        assert_eq!(
-            format!("{:?}", output),
-            format!(
-                "{:?}",
-                quote! {
-                    fn demo() {
-                        let a = Ignore1 {};
-                        let b = Ignore2 {};
-                        let c = Default1 {..::core::default::Default::default()};
-                        let d = Default2 {..::core::default::Default::default()};
-                    }
-                }
-            )
-        )
-    }
+#[derive(Debug, Default, PartialEq)]
+struct Config {
+    verbose: bool,
+    retries: u32,
+}
 
-    #[test]
-    fn only() {
-        let output = autodefault_impl(
-            quote! { only(Default1, Default2) },
-            quote! {
-                fn demo() {
-                    let a = Ignore1 {};
-                    let b = Ignore2 {};
-                    let c = Default1 {};
-                    let d = Default2 {};
-                }
-            },
-        );
+struct Plain {
+    value: u32,
+}
 
-        assert_eq!(
-            format!("{:?}", output),
-            format!(
-                "{:?}",
-                quote! {
-                    fn demo() {
-                        let a = Ignore1 {};
-                        let b = Ignore2 {};
-                        let c = Default1 {..::core::default::Default::default()};
-                        let d = Default2 {..::core::default::Default::default()};
-                    }
-                }
-            )
-        )
-    }
+fn retries(plain: Plain) -> u32 {
+    plain.value
+}
 
-    #[test]
-    fn inner_item() {
-        let input = quote! {
-            fn demo () {
-                let x = Foo {a: 10, b: 10};
+#[autodefault(bindings_only)]
+fn example() -> Config {
+    let mut config = Config { verbose: true };
+    config.retries = retries(Plain { value: 3 });
+    config
+}
 
-                fn inner () {
-                    let x = Foo {a: 10, b: 10};
-                }
-            }
-        };
-        let output: TokenStream2 = autodefault_impl(TokenStream2::new(), input);
+assert_eq!(example(), Config { verbose: true, retries: 3 });
+```
 
-        assert_eq!(
-            format!("{:?}", output),
-            format!(
-                "{:?}",
-                quote! {
-                    fn demo () {
-                        let x = Foo {a: 10, b: 10, ..::core::default::Default::default()};
+## Limiting nesting depth
 
-                        fn inner () {
-                            let x = Foo {a: 10, b: 10};
-                        }
-                    }
-                }
-            ),
+With `max_depth = N`, only struct expressions nested at most `N` deep inside
+other struct expressions are filled; deeper ones are left as written. The
+outermost struct expression has a depth of 1:
+
+```
+use autodefault::autodefault;
+
+#[derive(Debug, Default, PartialEq)]
+struct Leaf {
+    a: i32,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Middle {
+    leaf: Leaf,
+    b: i32,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Outer {
+    middle: Middle,
+    c: i32,
+}
+
+#[autodefault(max_depth = 2)]
+fn example() -> Outer {
+    // `Leaf` is at depth 3, so all of its fields must be given
+    Outer {
+        middle: Middle {
+            leaf: Leaf { a: 1 },
+        },
+    }
+}
+
+assert_eq!(example().middle.leaf, Leaf { a: 1 });
+```
+
+Use `outermost` to fill only the top-level struct expression in each nest,
+leaving every struct expression inside of it fully explicit. This is the same
+as `max_depth = 1`, and is handy for things like Bevy bundles, where you want
+the bundle itself defaulted but its components spelled out.
+
+`innermost` is the opposite: it only fills leaf struct expressions, which
+don't contain any other struct expressions, and leaves the containers for you
+to write out.
+
+## Skipping types everywhere
+
+If a type should never be filled by `autodefault`, you can tag its definition
+with `#[autodefault::skip]`. When that definition is inside a module (or
+function) that `#[autodefault]` is applied to, literals of that type are left
+alone, without needing an `except`:
+
+```
+use autodefault::autodefault;
+
+#[autodefault]
+mod fds {
+    #[autodefault::skip]
+    pub struct RawFd {
+        pub fd: i32,
+    }
+
+    pub fn example() -> RawFd {
+        RawFd { fd: 0 }
+    }
+}
+```
+
+Procedural macros can't look up type definitions, so `#[autodefault]` only
+sees the `#[autodefault::skip]` markers on structs defined in the item it's
+applied to; elsewhere, the marker does nothing. Within that item, types are
+matched by name, so any other type with the same name is also skipped. For
+types defined elsewhere, use `except`.
+
+Unit structs, like `struct Marker;`, have nothing to fill, and
+`Marker { ..Default::default() }` trips clippy's
+`default_constructed_unit_structs` lint. List them with
+`ignore_unit_like(...)` to never fill them, and to also replace their empty
+struct expressions with just the name, so `Marker {}` becomes `Marker`:
+
+```
+use autodefault::autodefault;
+
+#[derive(Debug, Default, PartialEq)]
+struct Marker;
+
+#[derive(Debug, Default, PartialEq)]
+struct Tagged {
+    marker: Marker,
+    value: i32,
+}
+
+#[autodefault(ignore_unit_like(Marker))]
+fn example() -> Tagged {
+    Tagged { marker: Marker {} }
+}
+
+assert_eq!(example(), Tagged { marker: Marker, value: 0 });
+```
+
+Since that replacement is only valid for unit structs, don't list structs
+declared with empty braces, like `struct Marker {}`.
+
+## Skipping a single struct expression
+
+To leave just one struct expression alone, tag it with `#[autodefault(skip)]`.
+`autodefault` removes the marker, so this works anywhere a struct expression
+can appear, even though attributes on expressions usually aren't allowed:
+
+```
+use autodefault::autodefault;
+
+#[derive(Default)]
+struct HasDefault {
+    a: i32,
+    b: i32,
+}
+
+struct NoDefault {
+    a: i32,
+}
+
+#[autodefault]
+fn example() {
+    let _a = HasDefault { a: 10 };
+    let _b = #[autodefault(skip)] NoDefault { a: 10 };
+}
+```
+
+The marker only applies to the struct expression it's attached to; any struct
+expressions nested inside of it are still filled as usual. To protect a whole
+region instead, wrap it in `autodefault::verbatim!`, which expands to its
+contents unchanged. `autodefault` never looks inside it, even if `verbatim`
+is listed in `macros`:
+
+```
+use autodefault::{autodefault, verbatim};
+
+#[derive(Default)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+struct NoDefault {
+    a: i32,
+}
+
+#[autodefault]
+fn example() -> i32 {
+    let point = Point { x: 1 };
+    let value = verbatim! {
+        NoDefault { a: point.x + 1 }
+    };
+    value.a
+}
+
+assert_eq!(example(), 2);
+```
+
+## Empty struct expressions
+
+An empty struct expression like `Config {}` is filled like any other, into
+`Config { ..Default::default() }`. To change that, use `empty = skip` to leave
+them alone, or `empty = error` to make them an error, for codebases that would
+rather spell these as `Config::default()`:
+
+```compile_fail
+use autodefault::autodefault;
+
+#[derive(Default)]
+struct Config {
+    verbose: bool,
+}
+
+#[autodefault(empty = error)]
+fn example() -> Config {
+    // error: empty struct expression; use Config::default() instead
+    Config {}
+}
+```
+
+`empty = fill` is the default.
+
+## Different rules for a block
+
+To use different rules for part of a function, tag a block inside of it with
+`#[autodefault(only(...))]` or `#[autodefault(except(...))]`. Struct
+expressions in that block use those rules instead of the function's; all the
+other options are unchanged:
+
+```
+use autodefault::autodefault;
+
+#[derive(Default)]
+struct HasDefault {
+    a: i32,
+    b: i32,
+}
+
+struct NoDefault {
+    a: i32,
+}
+
+#[autodefault]
+fn example() {
+    let _a = HasDefault { a: 10 };
+
+    #[autodefault(except(NoDefault))]
+    {
+        let _b = HasDefault { a: 10 };
+        let _c = NoDefault { a: 10 };
+    }
+}
+```
+
+The same attribute can be put on a `let` statement, to change the rules for
+just that statement's initializer. On either, `#[autodefault]` with no
+arguments fills every struct expression, whatever the function's rules are:
+
+```
+use autodefault::autodefault;
+
+#[derive(Debug, Default, PartialEq)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[autodefault(except(Point))]
+fn example() -> Vec<Point> {
+    #[autodefault]
+    let make = |x| Point { x };
+
+    vec![make(1), make(2), Point { x: 3, y: 3 }]
+}
+
+assert_eq!(example()[0], Point { x: 1, y: 0 });
+```
+
+Blocks and `let` statements are the only statements that can be tagged this
+way. On nightly, with `#![feature(proc_macro_hygiene)]`, `#[autodefault]` can
+also be applied to a `let` statement in a function that isn't annotated,
+which processes just that statement.
+
+## Filling a `match` expression
+
+Outside of an annotated function, `autodefault_match!` builds a `match`
+expression in which every struct expression is filled. It takes the
+scrutinee, followed by a comma and the arms. Patterns are never touched:
+
+```
+use autodefault::autodefault_match;
+
+#[derive(Debug, Default, PartialEq)]
+struct Shape {
+    sides: u32,
+    size: u32,
+}
+
+fn shape(name: &str) -> Shape {
+    autodefault_match!(name,
+        "triangle" => Shape { sides: 3 },
+        "square" => Shape { sides: 4 },
+        _ => Shape { size: 1 },
+    )
+}
+
+assert_eq!(shape("square"), Shape { sides: 4, size: 0 });
+```
+
+## Looking inside macros
+
+Macro arguments are arbitrary tokens, so by default `autodefault` leaves them
+alone. `macros(...)` names macros whose arguments should be processed anyway:
+
+```
+use autodefault::autodefault;
+
+#[derive(Debug, Default)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[autodefault(macros(format, vec))]
+fn example() -> String {
+    let points = vec![Point { x: 1 }, Point { y: 2 }];
+    format!("{:?} and {:?}", points, Point { x: 3 })
+}
+
+assert_eq!(
+    example(),
+    "[Point { x: 1, y: 0 }, Point { x: 0, y: 2 }] and Point { x: 3, y: 0 }",
+);
+```
+
+This is best-effort: the arguments are split on commas, and each one is
+processed as an expression. A leading `name =` or `name:` (as in named format
+arguments or `tracing`'s `target:`) is skipped, along with a `?` or `%`
+sigil, so `tracing::info!(value = ?Point { x: 1 })` works with
+`macros(info)`. If any argument isn't an expression after that (like
+`vec![x; 3]`, or a closure taking several arguments), the macro is left
+untouched. Macros are matched by the last component of their path.
+
+## Replacing hand-written `..Default::default()`
+
+Struct expressions that already end in a `..Default::default()` written by
+hand are normally left alone. With `strip_redundant`, `autodefault` removes
+those (however `Default` is qualified) from any struct expression it would
+fill, and then fills it as usual, so that every filled struct expression ends
+up with the same `..rest`. Other kinds of `..rest`, like `..base`, are left
+untouched.
+
+## Removing `autodefault`
+
+`strip` does the opposite of the usual transformation: instead of adding
+`..Default::default()` to struct expressions, it removes it (however
+`Default` is qualified) from every struct expression that would otherwise be
+filled, along with the comma before it. Other kinds of `..rest` are left
+alone. Combined with `debug`, this prints the code as it would be written
+without `..Default::default()`, which can help with migrating a function
+between the two styles. Since `strip` doesn't fill anything, it can't be
+combined with `require_effect`.
+
+## Only in tests
+
+With `test_only`, the item is only processed in `cfg(test)` builds. This is
+done by emitting it twice: once processed, under `#[cfg(test)]`, and once as
+written, under `#[cfg(not(test))]` (with any `#[autodefault(...)]` markers
+inside it removed). This way, test helpers can rely on `autodefault`, while
+the same code in regular builds has to spell out every field.
+
+```
+use autodefault::autodefault;
+
+#[derive(Debug, Default, PartialEq)]
+struct Config {
+    verbose: bool,
+    retries: u32,
+}
+
+#[autodefault(test_only)]
+fn example() -> Config {
+    Config { verbose: true, retries: 3 }
+}
+
+assert_eq!(example(), Config { verbose: true, retries: 3 });
+```
+
+## Catching unnecessary annotations
+
+A struct expression that already has a `..rest` is left alone, which can make
+the annotation misleading. With `exhaustive`, any such struct expression that
+`autodefault` would otherwise have filled is an error instead:
+
+```compile_fail
+use autodefault::autodefault;
+
+#[derive(Default)]
+struct Config {
+    verbose: bool,
+    retries: u32,
+}
+
+#[autodefault(exhaustive)]
+fn example(base: Config) -> Config {
+    // error: autodefault did nothing here
+    Config { verbose: true, ..base }
+}
+```
+
+Similarly, a misspelled or outdated entry in `only` or `except` silently
+matches nothing. With `check_filters`, every entry that doesn't match any
+struct expression in the annotated item is an error:
+
+```compile_fail
+use autodefault::autodefault;
+
+#[derive(Default)]
+struct Config {
+    verbose: bool,
+    retries: u32,
+}
+
+// error: 'Confg' didn't match any struct expression
+#[autodefault(only(Confg), check_filters)]
+fn example() -> Config {
+    Config { verbose: true }
+}
+```
+
+Finally, an annotation on a function that no longer has any struct
+expressions to fill is probably a leftover. With `require_effect`, it's an
+error for `autodefault` to not fill anything in the annotated item:
+
+```compile_fail
+use autodefault::autodefault;
+
+// error: #[autodefault] didn't fill any struct expressions here
+#[autodefault(require_effect)]
+fn example() -> u32 {
+    3
+}
+```
+
+# Filling with something other than `Default`
+
+If your types provide their defaults some other way, you can use `with` to
+give a function that should be called instead of `Default::default()`:
+
+```
+use autodefault::autodefault;
+
+#[derive(Debug, PartialEq)]
+struct Config {
+    retries: u32,
+    verbose: bool,
+}
+
+impl Config {
+    fn builder_defaults() -> Self {
+        Config { retries: 3, verbose: false }
+    }
+}
+
+#[autodefault(with = Config::builder_defaults)]
+fn example() -> Config {
+    Config { verbose: true }
+}
+
+assert_eq!(example(), Config { retries: 3, verbose: true });
+```
+
+If the function is in scope where the annotated function is defined, you can
+also give just its name with `from`, like `#[autodefault(from = sane_defaults)]`,
+which fills with `..sane_defaults()`. Either way, the function is called
+directly rather than through a trait, and it can be combined with `only` and
+`except` like any other fill.
+
+If the fill isn't a plain function call, you can give the whole `..rest` as
+a string with `rest_tokens`, like
+`#[autodefault(rest_tokens("..MyDefault::<Config>::build()"))]`. The leading
+`..` is optional, and the string must parse as an expression.
+
+You can also choose the fill for specific types with `fill`. Types that
+aren't listed use `Default::default()` (or the `with` function, if given),
+unless you also pass `fill_only`, in which case they're left alone:
+
+```
+use autodefault::autodefault;
+
+#[derive(Debug, PartialEq)]
+struct Config {
+    retries: u32,
+    verbose: bool,
+}
+
+impl Config {
+    fn prod() -> Self {
+        Config { retries: 3, verbose: false }
+    }
+}
+
+#[derive(Debug, PartialEq, Default)]
+struct Window {
+    width: u32,
+    height: u32,
+}
+
+struct App {
+    config: Config,
+    window: Window,
+}
+
+#[autodefault(fill(Config = Config::prod(), Window = Window { width: 800, height: 600 }), fill_only)]
+fn example() -> App {
+    App {
+        config: Config { verbose: true },
+        window: Window { height: 400 },
+    }
+}
+
+let app = example();
+assert_eq!(app.config, Config { retries: 3, verbose: true });
+assert_eq!(app.window, Window { width: 800, height: 400 });
+```
+
+## Choosing the path to `Default`
+
+By default, `autodefault` inserts the fully qualified
+`::core::default::Default::default()`, which works everywhere, including in
+`no_std` crates. If you'd prefer it go through `std` instead, use
+`path = std`:
+
+```
+use autodefault::autodefault;
+
+#[derive(Default)]
+struct Config {
+    retries: u32,
+    verbose: bool,
+}
+
+#[autodefault(path = std)]
+fn example() -> Config {
+    Config { retries: 3 }
+}
+```
+
+The `alloc` crate doesn't provide `Default`, so `no_std` crates should stick
+with the default of `core`. If you need it to go through some other path
+entirely, such as a prelude module that re-exports `Default`, you can give
+the full path to the trait as a string:
+
+```
+use autodefault::autodefault;
+
+mod prelude {
+    pub use core::default::Default;
+}
+
+#[derive(Default)]
+struct Config {
+    retries: u32,
+    verbose: bool,
+}
+
+#[autodefault(path = "prelude::Default")]
+fn example() -> Config {
+    Config { retries: 3 }
+}
+```
+
+Finally, `unqualified` inserts a plain `Default::default()`, which uses
+whatever `Default` is in scope where the function is defined. This is useful
+if your prelude shadows `Default` with a trait of your own. To name that trait
+explicitly instead, use `fill_trait = my_crate::Default` (described below),
+which inserts `my_crate::Default::default()` (or
+`<Type as my_crate::Default>::default()` with `typed`).
+
+## Filling through another trait
+
+To fill through a method of some other trait, like `bytemuck::Zeroable`,
+give the trait with `fill_trait` and its method with `fill_method`. The method
+must take no arguments and return `Self`. Like `Default::default()`, this is
+inserted as `Trait::method()`, or as `<Type as Trait>::method()` with `typed`:
+
+```
+use autodefault::autodefault;
+
+trait Zeroable: Sized {
+    fn zeroed() -> Self;
+}
+
+#[derive(Debug, PartialEq)]
+struct Pixel {
+    r: u8,
+    g: u8,
+    b: u8,
+}
+
+impl Zeroable for Pixel {
+    fn zeroed() -> Self {
+        Pixel { r: 0, g: 0, b: 0 }
+    }
+}
+
+#[autodefault(fill_trait = Zeroable, fill_method = zeroed, typed)]
+fn red() -> Pixel {
+    Pixel { r: 255 }
+}
+
+assert_eq!(red(), Pixel { r: 255, g: 0, b: 0 });
+```
+
+If your types instead have an inherent constructor, like a `new()` that takes
+no arguments, use `inherent = new`. This inserts `..Type::new()`, using the
+path of each struct expression:
+
+```
+use autodefault::autodefault;
+
+#[derive(Debug, PartialEq)]
+struct Pixel {
+    r: u8,
+    g: u8,
+    b: u8,
+}
+
+impl Pixel {
+    fn new() -> Self {
+        Pixel { r: 0, g: 0, b: 0 }
+    }
+}
+
+#[autodefault(inherent = new)]
+fn red() -> Pixel {
+    Pixel { r: 255 }
+}
+
+assert_eq!(red(), Pixel { r: 255, g: 0, b: 0 });
+```
+
+Only one of `with`, `from`, `inherent`, `rest_tokens`, `path`, `unqualified`,
+and `fill_trait` may be given, since they each pick a different fill.
+`fill_method` can only be combined with `path`, `unqualified`, or
+`fill_trait`.
+
+## Helping type inference
+
+Occasionally, a bare `Default::default()` can't figure out what type it's
+supposed to produce, especially in generic code. With `typed`, `autodefault`
+instead inserts `..<Type as Default>::default()`, using the path of the struct
+expression itself:
+
+```
+use autodefault::autodefault;
+
+#[derive(Debug, Default, PartialEq)]
+struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+#[autodefault(typed)]
+fn example() -> Grid<u8> {
+    Grid::<u8> { width: 3 }
+}
+
+assert_eq!(example().width, 3);
+```
+
+Because the struct's own path is used as the type, generic structs must be
+written with their generic arguments (`Grid::<u8> { .. }`) in this mode. With
+`with` or `from`, `typed` anchors the function's result to the type instead,
+as `..::core::convert::identity::<Type>(func())`.
+
+## `const fn`
+
+`Default::default()` can't be called in a `const fn`, so `autodefault` will
+report an error if it would insert one there. Instead, you can give a
+`const fn` to use as the fill in `const` functions with `const_default`:
+
+```
+use autodefault::autodefault;
+
+#[derive(Debug, PartialEq)]
+struct Config {
+    retries: u32,
+    verbose: bool,
+}
+
+impl Config {
+    const fn const_default() -> Self {
+        Config { retries: 3, verbose: false }
+    }
+}
+
+#[autodefault(const_default = Config::const_default)]
+const fn example() -> Config {
+    Config { verbose: true }
+}
+
+const CONFIG: Config = example();
+assert_eq!(CONFIG, Config { retries: 3, verbose: true });
+```
+
+```compile_fail
+use autodefault::autodefault;
+
+#[derive(Default)]
+struct Config {
+    retries: u32,
+    verbose: bool,
+}
+
+#[autodefault]
+const fn example() -> Config {
+    Config { verbose: true }
+}
+```
+
+`const_default` only applies to `const fn`s, so it can be combined with a
+normal fill when `#[autodefault]` is applied to a module containing both.
+
+The same goes for the length of an array repeat expression, like
+`[0; Config {}.retries as usize]`, which is also evaluated at compile time.
+The repeated element itself is filled as usual.
+
+Struct expressions in const generic arguments, like `Foo::<{ Bar { x: 1 } }>`,
+are left alone unless `const_default` is given, in which case it's used as
+their fill.
+
+## Filling specific fields
+
+If there are fields you always want set to something other than their
+default, you can give them with `field`. These are added to every filled
+struct expression that doesn't already set that field, ahead of the
+`..Default::default()`:
+
+```
+use autodefault::autodefault;
+
+#[derive(Debug, Default)]
+struct User {
+    id: u64,
+    name: String,
+    admin: bool,
+}
+
+fn next_id() -> u64 {
+    42
+}
+
+#[autodefault(field(id = next_id()))]
+fn example() -> (User, User) {
+    (User { name: "alice".to_owned() }, User { id: 1, admin: true })
+}
+
+let (alice, root) = example();
+assert_eq!(alice.id, 42);
+assert_eq!(root.id, 1);
+```
+
+Like `..Default::default()` itself, these fields are added blindly, since
+`autodefault` can't know which fields a type actually has. Use `only` or
+`except` to keep them away from types that don't have those fields.
+
+The added fields go after the fields that are already there, in the order
+they're given in `field(...)`; the existing fields are never reordered. If the
+same field is given more than once, the last one wins. To put the added
+fields first instead, use `fields_first`; the `..Default::default()` (or a
+`..rest` you wrote yourself) always stays last. Since field initializers are
+evaluated in the order they're written, this also means the added fields'
+expressions are evaluated first.
+
+Enum variants can't be built with `..Default::default()`. Instead, a variant
+that's listed by its full path in `only`, like `only(Event::Click)`, gets just
+the fields from `field(...)` (that it doesn't already set), and no `..rest` at
+all. Other variants are left alone:
+
+```
+use autodefault::autodefault;
+
+#[derive(Debug, PartialEq)]
+enum Event {
+    Click { x: i32, y: i32, id: u64 },
+    Key { code: u32, id: u64 },
+}
+
+#[autodefault(only(Event::Click), field(id = 0))]
+fn example() -> [Event; 2] {
+    [Event::Click { x: 1, y: 2 }, Event::Key { code: 13, id: 7 }]
+}
+
+assert_eq!(example()[0], Event::Click { x: 1, y: 2, id: 0 });
+```
+
+Conversely, if there are fields that must never be silently defaulted, name
+them with `require_field(...)`. It's an error for any struct expression that
+`autodefault` fills to leave out one of those fields:
+
+```compile_fail
+use autodefault::autodefault;
+
+#[derive(Default)]
+struct Login {
+    user: String,
+    password: String,
+}
+
+#[autodefault(require_field(password))]
+fn example() -> Login {
+    // error: The field 'password' must be set explicitly
+    Login { user: "root".to_owned() }
+}
+```
+
+## Filling from a template
+
+For a type that doesn't implement `Default`, but whose fields all have
+sensible values, give a template with `template(...)`. Struct expressions of
+that type get every field from the template that they don't already set,
+rather than a `..rest`, so they're still complete without `Default`:
+
+```
+use autodefault::autodefault;
+use std::time::Duration;
+
+#[derive(Debug, PartialEq)]
+struct Config {
+    retries: u32,
+    timeout: Duration,
+    verbose: bool,
+}
+
+#[autodefault(template(Config { retries: 3, timeout: Duration::from_secs(30), verbose: false }))]
+fn example() -> Config {
+    Config { verbose: true }
+}
+
+assert_eq!(
+    example(),
+    Config { retries: 3, timeout: Duration::from_secs(30), verbose: true },
+);
+```
+
+Templates are matched by the last component of their path, and the template's
+field expressions are copied into each struct expression, so they're
+evaluated separately for each one. Struct expressions inside of them are
+filled like any others.
+
+## Silencing clippy
+
+Clippy's pedantic `default_trait_access` lint complains about every
+`Default::default()` that `autodefault` inserts. Use `allow_clippy` to add an
+`#[allow(clippy::default_trait_access)]` to the function (if it doesn't
+already have one). Alternatively, `typed` avoids the lint entirely, since it
+names the type being defaulted.
+
+Similarly, some versions of the compiler report the fully qualified
+`::core::default::Default::default()` under the `unused_qualifications` lint.
+If you deny that lint, use `allow_qualification` to add an
+`#[allow(unused_qualifications)]` to the function in the same way:
+
+```
+#![deny(unused_qualifications)]
+
+use autodefault::autodefault;
+
+#[derive(Debug, Default, PartialEq)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[autodefault(allow_qualification)]
+fn example() -> Point {
+    Point { x: 1 }
+}
+
+assert_eq!(example(), Point { x: 1, y: 0 });
+```
+
+# Applying to modules and `impl` blocks
+
+`#[autodefault]` can also be applied to an inline `mod`, in which case it
+applies to every function, `impl` block, and trait defined directly in that
+module. This works
+especially well with `only_derive`, since it can see every struct defined in
+that module:
+
+```
+use autodefault::autodefault;
+
+#[autodefault(only_derive)]
+mod shapes {
+    #[derive(Debug, Default, PartialEq)]
+    pub struct Point {
+        pub x: i32,
+        pub y: i32,
+    }
+
+    pub struct Line {
+        pub start: Point,
+        pub end: Point,
+    }
+
+    pub fn unit_x() -> Line {
+        Line {
+            start: Point {},
+            end: Point { x: 1 },
+        }
+    }
+}
+
+assert_eq!(shapes::unit_x().end, shapes::Point { x: 1, y: 0 });
+```
+
+Likewise, it can be applied to an `impl` block, in which case it applies to
+every method in that block. To pick which functions in a module or `impl`
+block are processed, use `only_fns(...)` or `except_fns(...)` with a list of
+function names; functions that aren't selected are left unfilled.
+These can be combined with the usual `only` and `except`, which then apply
+inside of the selected functions:
+
+```
+use autodefault::autodefault;
+
+#[derive(Debug, Default, PartialEq)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+struct Builder;
+
+#[autodefault(except_fns(origin))]
+impl Builder {
+    fn unit_x() -> Point {
+        Point { x: 1 }
+    }
+
+    fn origin() -> Point {
+        Point { x: 0, y: 0 }
+    }
+}
+
+assert_eq!(Builder::unit_x(), Point { x: 1, y: 0 });
+```
+
+In an `impl` block, `Self { .. }` is matched as the type the block is for, so
+`only(Widget)` in an `impl Widget` block covers `Self { .. }`, `Widget { .. }`,
+and `ui::Widget { .. }` alike. Likewise, `Self` can be used in `only` and
+`except` to mean that type.
+
+On a trait, `#[autodefault]` applies to the default bodies of its methods.
+It can't be applied to other kinds of items, like structs or `extern` blocks.
+
+## Sharing options across a module
+
+To avoid repeating the same options on every function in a module, give them
+to `#[autodefault_config(...)]` on the module instead. Every `#[autodefault]`
+in that module (including in nested modules and `impl` blocks) then uses those
+options, plus any of its own. A function's own `only` or `except` replaces
+the module's.
+
+This works by rewriting each `#[autodefault(...)]` inside the module to
+`#[autodefault(config(...), ...)]`, so `#[autodefault_config]` must be applied
+to the module directly, and only affects `#[autodefault]` attributes written
+inside of it:
+
+```
+use autodefault::autodefault_config;
+
+#[autodefault_config(except(NoDefault))]
+mod shapes {
+    use autodefault::autodefault;
+
+    #[derive(Debug, Default, PartialEq)]
+    pub struct Point {
+        pub x: i32,
+        pub y: i32,
+    }
+
+    pub struct NoDefault {
+        pub x: i32,
+    }
+
+    #[autodefault]
+    pub fn unit_x() -> Point {
+        let _unfilled = NoDefault { x: 1 };
+        Point { x: 1 }
+    }
+
+    #[autodefault(only(Point))]
+    pub fn unit_y() -> Point {
+        Point { y: 1 }
+    }
+}
+
+assert_eq!(shapes::unit_x(), shapes::Point { x: 1, y: 0 });
+assert_eq!(shapes::unit_y(), shapes::Point { x: 0, y: 1 });
+```
+
+Named filters from `define_filters!` can't be used in `#[autodefault_config]`;
+give `use(name)` to each function instead.
+
+## Reusing options by name
+
+To share a set of options between functions that aren't in the same module,
+give it a name with `define_filters!`, and then refer to it with `use(name)`.
+Any other options given alongside `use(name)` are applied on top, as with
+`#[autodefault_config]`:
+
+```
+use autodefault::{autodefault, define_filters};
+
+#[derive(Debug, Default, PartialEq)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+struct RawHandle {
+    fd: i32,
+}
+
+define_filters!(no_default = except(RawHandle));
+
+#[autodefault(use(no_default))]
+fn unit_x() -> Point {
+    let _handle = RawHandle { fd: 3 };
+    Point { x: 1 }
+}
+
+assert_eq!(unit_x(), Point { x: 1, y: 0 });
+```
+
+Several sets can be defined at once, separated by `;`. Under the hood,
+`define_filters!` defines a `macro_rules!` macro with each name, and
+`#[autodefault(use(name))]` passes the function to that macro, which applies
+`#[autodefault]` again with the named options added. This means the usual
+`macro_rules!` scoping applies: a named set can only be used after it's
+defined (or from other modules, with `#[macro_use]` or a `use` of the macro),
+and using a name that isn't defined is a "cannot find macro" error.
+
+In a module with `#[autodefault_config(...)]`, a function's named set is
+combined with the module's options:
+
+```
+use autodefault::{autodefault_config, define_filters};
+
+define_filters!(no_handles = except(RawHandle));
+
+#[autodefault_config(except(NoDefault))]
+mod shapes {
+    use autodefault::autodefault;
+
+    #[derive(Debug, Default, PartialEq)]
+    pub struct Point {
+        pub x: i32,
+        pub y: i32,
+    }
+
+    pub struct RawHandle {
+        pub fd: i32,
+    }
+
+    pub struct NoDefault {
+        pub x: i32,
+    }
+
+    #[autodefault(use(no_handles))]
+    pub fn unit_x() -> Point {
+        let _handle = RawHandle { fd: 3 };
+        let _unfilled = NoDefault { x: 1 };
+        Point { x: 1 }
+    }
+}
+
+assert_eq!(shapes::unit_x(), shapes::Point { x: 1, y: 0 });
+```
+
+# Debugging
+
+If `autodefault` isn't doing what you expect, add `debug` to its arguments
+(for example, `#[autodefault(only(Foo), debug)]`). It will print the
+transformed item to stderr during compilation, without otherwise changing
+anything.
+
+To check what `autodefault` would do before committing to it, use `dry_run`.
+Instead of transforming the item, it prints the name and location of each
+struct expression that would be filled, and leaves the item unchanged:
+
+```text
+autodefault: dry run found 2 struct expressions to fill:
+    Config at src/main.rs:12:9
+    Window at src/main.rs:14:17
+```
+
+Source locations are only available with the `span-locations` cargo feature
+enabled. Errors that a real run would report, like those from `require_field`
+or `check_filters`, are still reported.
+
+For a less noisy overview, `summary` prints a single line after processing,
+with the number of struct expressions that were filled and how many of each
+type:
+
+```text
+autodefault: filled 12 literals across 3 types: Foo(5), Bar(4), Baz(3)
+```
+
+To check the number of filled struct expressions from your own code (for
+instance, in a test that guards against refactors silently changing it), use
+`count_const = NAME`. This defines a `const NAME: usize` next to the item with
+the number of struct expressions that were filled:
+
+```
+use autodefault::autodefault;
+
+#[derive(Default)]
+struct Config {
+    verbose: bool,
+    retries: u32,
+}
+
+#[autodefault(count_const = EXAMPLE_FILLS)]
+fn example() -> (Config, Config) {
+    (Config { verbose: true }, Config { retries: 3 })
+}
+
+assert_eq!(EXAMPLE_FILLS, 2);
+```
+
+On nightly, you can instead use `warn` to attach a compiler warning to each
+struct expression that `autodefault` fills, so they show up alongside the rest
+of your build output. This requires the `nightly` cargo feature, since it
+uses the unstable diagnostic API; without it, `warn` does nothing.
+
+# Other behaviors
+
+`autodefault` will not descend into nested item definitions; if you nest an
+`fn` item inside another `fn`, you'll need to tag the inner function with
+`autodefault` again.
+
+```
+use autodefault::autodefault;
+
+#[derive(Default)]
+struct HasDefault {
+    x: i32
+}
+
+struct NoDefault {
+    x: i32
+}
+
+#[autodefault]
+fn outer() {
+    let _x = HasDefault {};
+
+    fn inner() {
+        let _x = NoDefault {x: 10};
+    }
+}
+```
+
+`autodefault` processes items recursively, so very deeply nested code (like
+struct expressions generated by another tool) could overflow the compiler's
+stack. To prevent this, items nested more than 128 levels deep (counting
+brackets, braces, and parentheses) are rejected with an error. If you need
+more, raise the limit with `recursion_limit = N`.
+
+Similarly, `max_literals = N` guards against accidentally applying
+`autodefault` to an enormous item, such as a generated module: if more than `N`
+struct expressions would be filled, it reports an error (with the actual
+count) instead of filling any of them.
+
+`#[autodefault]` also works on functions generated by `macro_rules!`,
+including ones where struct expressions, types, or field values come from
+the macro's arguments (like `$ty { a: $value }`, or a whole `$e:expr`).
+Expressions given in the options (like those in `fill(...)` or `with`) are
+resolved where they were written, so they can refer to the macro's own
+variables:
+
+```
+use autodefault::autodefault;
+
+#[derive(Debug, Default, PartialEq)]
+struct Config {
+    retries: u32,
+    verbose: bool,
+}
+
+fn base(retries: u32) -> Config {
+    Config {
+        retries,
+        verbose: true,
+    }
+}
+
+macro_rules! config_fn {
+    ($name:ident, $ty:ident) => {
+        #[autodefault(fill($ty = base(retries)))]
+        fn $name(retries: u32) -> $ty {
+            $ty { retries: retries + 1 }
+        }
+    };
+}
+
+config_fn!(make_config, Config);
+
+assert_eq!(make_config(2), Config { retries: 3, verbose: true });
+```
+
+When combining `#[autodefault]` with other attribute macros, like
+`#[tokio::test]`, put `#[autodefault]` last, closest to the function. Attribute
+macros are expanded from the top down, so this way `autodefault` sees the
+function as you wrote it, with the other attributes left in place.
+
+If `autodefault` can't process an item (for instance, because it doesn't parse
+yet, like while you're in the middle of typing a struct expression), it emits
+the item unchanged alongside the error. This keeps IDEs like rust-analyzer
+working inside the annotated item, so that field names are still completed.
+
+All of the code `autodefault` generates uses fully qualified paths, so it
+works in modules and crates with `#![no_implicit_prelude]`. The exception is
+`unqualified`, which emits a bare `Default` and so needs `Default` to be in
+scope.
+
+```
+mod strict {
+    #![no_implicit_prelude]
+
+    use ::autodefault::autodefault;
+
+    #[derive(Debug, ::core::default::Default, PartialEq)]
+    pub struct Foo {
+        pub a: i32,
+        pub b: i32,
+    }
+
+    #[autodefault(checked, typed, allow_clippy, count_const = FILLED)]
+    pub fn make() -> Foo {
+        let make = |a| Foo { a };
+        make(1)
+    }
+}
+
+assert_eq!(strict::make(), strict::Foo { a: 1, b: 0 });
+```
+
+*/
+
+#![cfg_attr(feature = "nightly", feature(proc_macro_diagnostic))]
+
+use std::{
+    collections::{HashMap, HashSet},
+    iter::FromIterator,
+    mem,
+};
+
+use proc_macro::TokenStream;
+use proc_macro2::{Group, Literal, Spacing, Span, TokenStream as TokenStream2, TokenTree};
+use quote::{quote, ToTokens};
+use syn::{
+    bracketed,
+    ext::IdentExt,
+    parenthesized,
+    parse::{Parse, Parser},
+    parse2, parse_quote,
+    punctuated::Punctuated,
+    spanned::Spanned,
+    visit_mut::{
+        visit_block_mut, visit_expr_async_mut, visit_expr_block_mut, visit_expr_closure_mut,
+        visit_expr_mut, visit_expr_return_mut, visit_expr_struct_mut, visit_generic_argument_mut,
+        visit_generic_method_argument_mut, visit_item_mut, visit_local_mut, visit_stmt_mut,
+        VisitMut,
+    },
+    Arm, Attribute, Block, Expr, ExprAsync, ExprBlock, ExprClosure, ExprIf, ExprMatch, ExprPath,
+    ExprRepeat, ExprReturn, ExprStruct, FieldValue, GenericArgument, GenericMethodArgument, Ident,
+    ImplItem, Item, ItemFn, ItemImpl, ItemMod, ItemTrait, LitInt, LitStr, Local, Macro, Member,
+    Pat, Path, ReturnType, Signature, Stmt, Token, TraitItem, TraitItemMethod, Type,
+};
+
+/// A single entry in an `only` or `except` list
+#[derive(Debug)]
+enum FilterEntry {
+    /// A plain type name, like `Foo`. Matches any struct expression whose
+    /// path ends with this name.
+    Name(Ident),
+
+    /// A path prefix, like `bevy::*`. Matches any struct expression whose path
+    /// starts with these segments.
+    Prefix(Vec<Segment>),
+
+    /// A full path, like `proto::_::Message`. Matches any struct expression
+    /// with exactly this path.
+    Path(Vec<Segment>),
+
+    /// A number of segments, like `segments = 2`. Matches any struct
+    /// expression whose path has exactly this many segments.
+    Segments(usize),
+}
+
+/// A single segment of a path in an `only` or `except` list; `None` is the
+/// `_` wildcard, which matches any one segment
+type Segment = Option<Ident>;
+
+fn parse_segment(input: syn::parse::ParseStream) -> syn::Result<Segment> {
+    if input.peek(Token![_]) {
+        input.parse::<Token![_]>()?;
+        Ok(None)
+    } else {
+        input.call(Ident::parse_any).map(Some)
+    }
+}
+
+/// Check if the segments of a path match a pattern of segments, which must
+/// have the same length
+fn segments_match<'a>(
+    pattern: &[Segment],
+    segments: impl IntoIterator<Item = &'a syn::PathSegment>,
+) -> bool {
+    pattern
+        .iter()
+        .zip(segments)
+        .all(|(pattern, segment)| match pattern {
+            Some(ident) => *ident == segment.ident,
+            None => true,
+        })
+}
+
+impl Parse for FilterEntry {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        if input.peek(Ident) && input.peek2(Token![=]) {
+            let name: Ident = input.parse()?;
+
+            if name != "segments" {
+                return Err(syn::Error::new(
+                    name.span(),
+                    "Expected a type name, a path, or 'segments = N'",
+                ));
+            }
+
+            input.parse::<Token![=]>()?;
+            let count: LitInt = input.parse()?;
+
+            return count.base10_parse().map(FilterEntry::Segments);
+        }
+
+        // Paths are matched without their leading `::`, if any
+        input.parse::<Option<Token![::]>>()?;
+
+        if !input.peek2(Token![::]) {
+            return input.call(Ident::parse_any).map(FilterEntry::Name);
+        }
+
+        let mut segments = vec![parse_segment(input)?];
+
+        while input.peek(Token![::]) {
+            input.parse::<Token![::]>()?;
+
+            if input.peek(Token![*]) {
+                input.parse::<Token![*]>()?;
+                return Ok(FilterEntry::Prefix(segments));
+            }
+
+            segments.push(parse_segment(input)?);
+        }
+
+        Ok(FilterEntry::Path(segments))
+    }
+}
+
+/// The set of types named by an `only` or `except` list. These are kept in
+/// the order they were given (without duplicates), so that anything that
+/// lists them is reproducible.
+#[derive(Debug, Default)]
+struct Filter {
+    names: Vec<Ident>,
+    prefixes: Vec<Vec<Segment>>,
+    paths: Vec<Vec<Segment>>,
+    segment_counts: Vec<usize>,
+}
+
+impl Filter {
+    /// Add all the entries of another filter to this one
+    fn merge(&mut self, other: Filter) {
+        self.extend(other.names.into_iter().map(FilterEntry::Name));
+        self.extend(other.prefixes.into_iter().map(FilterEntry::Prefix));
+        self.extend(other.paths.into_iter().map(FilterEntry::Path));
+        self.extend(other.segment_counts.into_iter().map(FilterEntry::Segments));
+    }
+
+    /// Describe each entry in this filter that doesn't match any of these
+    /// paths, along with where it was written
+    fn unmatched(&self, paths: &[Path]) -> Vec<(String, Span)> {
+        let describe = |pattern: &[Segment]| {
+            let segments: Vec<String> = pattern
+                .iter()
+                .map(|segment| match segment {
+                    Some(ident) => ident.to_string(),
+                    None => "_".to_owned(),
+                })
+                .collect();
+
+            let span = pattern
+                .iter()
+                .flatten()
+                .next()
+                .map_or_else(Span::call_site, Ident::span);
+
+            (segments.join("::"), span)
+        };
+
+        let names = self
+            .names
+            .iter()
+            .filter(|name| {
+                !paths
+                    .iter()
+                    .any(|path| path.segments.last().unwrap().ident == **name)
+            })
+            .map(|name| (name.to_string(), name.span()));
+
+        let prefixes = self
+            .prefixes
+            .iter()
+            .filter(|prefix| {
+                !paths.iter().any(|path| {
+                    prefix.len() < path.segments.len() && segments_match(prefix, &path.segments)
+                })
+            })
+            .map(|prefix| {
+                let (text, span) = describe(prefix);
+                (text + "::*", span)
+            });
+
+        let full_paths = self
+            .paths
+            .iter()
+            .filter(|pattern| {
+                !paths.iter().any(|path| {
+                    pattern.len() == path.segments.len() && segments_match(pattern, &path.segments)
+                })
+            })
+            .map(|pattern| describe(pattern));
+
+        let segment_counts = self
+            .segment_counts
+            .iter()
+            .filter(|count| !paths.iter().any(|path| path.segments.len() == **count))
+            .map(|count| (format!("segments = {}", count), Span::call_site()));
+
+        names
+            .chain(prefixes)
+            .chain(full_paths)
+            .chain(segment_counts)
+            .collect()
+    }
+
+    /// Check if this filter has a full path entry (not just a name or a
+    /// prefix) that matches this path
+    fn lists_path(&self, path: &Path) -> bool {
+        self.paths.iter().any(|pattern| {
+            pattern.len() == path.segments.len() && segments_match(pattern, &path.segments)
+        })
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        let struct_ident = &path.segments.last().unwrap().ident;
+
+        self.names.contains(struct_ident)
+            || self.prefixes.iter().any(|prefix| {
+                prefix.len() < path.segments.len() && segments_match(prefix, &path.segments)
+            })
+            || self.lists_path(path)
+            || self.segment_counts.contains(&path.segments.len())
+    }
+}
+
+/// Add an item to a list, unless it's already there
+fn push_new<T: PartialEq>(list: &mut Vec<T>, item: T) {
+    if !list.contains(&item) {
+        list.push(item);
+    }
+}
+
+impl Extend<FilterEntry> for Filter {
+    fn extend<I: IntoIterator<Item = FilterEntry>>(&mut self, entries: I) {
+        for entry in entries {
+            match entry {
+                FilterEntry::Name(name) => push_new(&mut self.names, name),
+                FilterEntry::Prefix(prefix) => push_new(&mut self.prefixes, prefix),
+                FilterEntry::Path(path) => push_new(&mut self.paths, path),
+                FilterEntry::Segments(count) => push_new(&mut self.segment_counts, count),
+            }
+        }
+    }
+}
+
+impl FromIterator<FilterEntry> for Filter {
+    fn from_iter<I: IntoIterator<Item = FilterEntry>>(entries: I) -> Self {
+        let mut filter = Filter::default();
+        filter.extend(entries);
+        filter
+    }
+}
+
+#[derive(Debug, Default)]
+enum Rules {
+    #[default]
+    All,
+    Only(Filter),
+    Except(Filter),
+
+    /// An ordered list of `only` and `except` clauses, from `rules(...)`. The
+    /// last clause that matches a struct expression decides whether it's
+    /// filled.
+    Clauses(Vec<Clause>),
+}
+
+/// A single `only(...)` or `except(...)` clause in `rules(...)`
+#[derive(Debug)]
+struct Clause {
+    /// True for `only`, which fills the types it matches, and false for
+    /// `except`, which doesn't
+    fill: bool,
+    filter: Filter,
+}
+
+impl Rules {
+    /// The filters of all of these rules, in order
+    fn filters(&self) -> Vec<&Filter> {
+        match self {
+            Rules::All => Vec::new(),
+            Rules::Only(filter) | Rules::Except(filter) => vec![filter],
+            Rules::Clauses(clauses) => clauses.iter().map(|clause| &clause.filter).collect(),
+        }
+    }
+}
+
+/// Parse the parenthesized list following `only` or `except`
+fn parse_filter(input: syn::parse::ParseStream) -> syn::Result<Filter> {
+    let content;
+    let _parens = parenthesized!(content in input);
+
+    let entries: Punctuated<FilterEntry, Token![,]> = Punctuated::parse_terminated(&content)?;
+
+    Ok(entries.into_iter().collect())
+}
+
+/// Parse the parenthesized array of string literals following `only_from`
+/// or `except_from`, like `only_from(["Foo", "bevy::*"])`
+fn parse_filter_from(input: syn::parse::ParseStream) -> syn::Result<Filter> {
+    let content;
+    let _parens = parenthesized!(content in input);
+
+    let array;
+    let _brackets = bracketed!(array in content);
+    content.parse::<Option<Token![,]>>()?;
+
+    let names: Punctuated<LitStr, Token![,]> = Punctuated::parse_terminated(&array)?;
+    let entries = names
+        .iter()
+        .map(LitStr::parse)
+        .collect::<syn::Result<Vec<FilterEntry>>>()?;
+
+    Ok(entries.into_iter().collect())
+}
+
+/// Parse the parenthesized list of clauses following `rules`, like
+/// `rules(only(ui::*), except(ui::RawHandle))`
+fn parse_clauses(input: syn::parse::ParseStream) -> syn::Result<Rules> {
+    let content;
+    let _parens = parenthesized!(content in input);
+
+    let mut clauses = Vec::new();
+
+    while !content.is_empty() {
+        let kind: Ident = content.parse()?;
+
+        let filter = match kind.to_string().as_str() {
+            "only" | "except" => parse_filter(&content)?,
+            "only_from" | "except_from" => parse_filter_from(&content)?,
+            _ => {
+                return Err(syn::Error::new(
+                    kind.span(),
+                    "Expected an 'only' or 'except' clause",
+                ))
+            }
+        };
+
+        clauses.push(Clause {
+            fill: kind.to_string().starts_with("only"),
+            filter,
+        });
+
+        if !content.is_empty() {
+            content.parse::<Token![,]>()?;
+        }
+    }
+
+    Ok(Rules::Clauses(clauses))
+}
+
+fn make_rules(only: bool, filter: Filter) -> Rules {
+    match only {
+        true => Rules::Only(filter),
+        false => Rules::Except(filter),
+    }
+}
+
+impl Rules {
+    /// Combine these rules with more rules of the same kind, so that `only`
+    /// and `only_from` (for instance) can be used together
+    fn merge(&mut self, rules: Rules, span: Span) -> syn::Result<()> {
+        match (self, rules) {
+            (current @ Rules::All, rules) => *current = rules,
+            (Rules::Only(current), Rules::Only(filter))
+            | (Rules::Except(current), Rules::Except(filter)) => current.merge(filter),
+            (Rules::Clauses(current), Rules::Clauses(clauses)) => current.extend(clauses),
+            (Rules::Clauses(_), _) | (_, Rules::Clauses(_)) => {
+                return Err(syn::Error::new(
+                    span,
+                    "'rules' can't be combined with 'except' or 'only'; give them as \
+                    clauses in 'rules' instead",
+                ))
+            }
+            _ => {
+                return Err(syn::Error::new(
+                    span,
+                    "Only one of 'except' or 'only' may be given",
+                ))
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Parse the arguments to an `#[autodefault(...)]` attribute on a block or
+/// `let` statement, which can only override the `only`, `except`, or `rules`
+/// rules
+fn parse_override_rules(input: syn::parse::ParseStream) -> syn::Result<Rules> {
+    if input.is_empty() {
+        return Ok(Rules::All);
+    }
+
+    let kind: Ident = input.parse()?;
+
+    let rules = match kind.to_string().as_str() {
+        "only" | "except" => make_rules(kind == "only", parse_filter(input)?),
+        "rules" => parse_clauses(input)?,
+        _ => {
+            return Err(syn::Error::new(
+                kind.span(),
+                "Only 'except', 'only', or 'rules' can be given for a block or statement",
+            ))
+        }
+    };
+    input.parse::<Option<Token![,]>>()?;
+
+    Ok(rules)
+}
+
+/// What to do with empty struct expressions, like `Foo {}`, from
+/// `empty = ...`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Empty {
+    #[default]
+    Fill,
+    Skip,
+    Error,
+}
+
+impl Parse for Empty {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let policy: Ident = input.parse()?;
+
+        match policy.to_string().as_str() {
+            "fill" => Ok(Empty::Fill),
+            "skip" => Ok(Empty::Skip),
+            "error" => Ok(Empty::Error),
+            _ => Err(syn::Error::new(
+                policy.span(),
+                "Expected 'fill', 'skip', or 'error'",
+            )),
+        }
+    }
+}
+
+/// The expression inserted as the `..rest` of a struct expression
+enum Fill {
+    /// A call to a trait method with no arguments, through the given path to
+    /// the trait. This is usually `::core::default::Default::default()`, but
+    /// can be changed with `fill_trait` and `fill_method`.
+    Trait(Path, Ident),
+
+    /// A call to a function with no arguments, from `with = path::to::func`
+    /// or `from = func`
+    Call(Path),
+
+    /// An arbitrary expression, from `rest_tokens("..expr")`
+    Expr(Box<Expr>),
+
+    /// A call to an associated function with no arguments on the struct
+    /// expression's own type, from `inherent = method`
+    Inherent(Ident),
+}
+
+impl Default for Fill {
+    fn default() -> Self {
+        Fill::Trait(
+            parse_quote! { ::core::default::Default },
+            parse_quote! { default },
+        )
+    }
+}
+
+impl Fill {
+    /// Change the trait used by this fill, keeping its method
+    fn set_trait(&mut self, fill_trait: Path) {
+        let method = match mem::take(self) {
+            Fill::Trait(_, method) => method,
+            Fill::Call(_) | Fill::Expr(_) | Fill::Inherent(_) => parse_quote! { default },
+        };
+
+        *self = Fill::Trait(fill_trait, method);
+    }
+
+    /// Change the trait method called by this fill, keeping its trait
+    fn set_method(&mut self, method: Ident) {
+        let fill_trait = match mem::take(self) {
+            Fill::Trait(fill_trait, _) => fill_trait,
+            Fill::Call(_) | Fill::Expr(_) | Fill::Inherent(_) => {
+                parse_quote! { ::core::default::Default }
+            }
+        };
+
+        *self = Fill::Trait(fill_trait, method);
+    }
+
+    /// Build the fill expression for a struct expression with the given
+    /// path. If `typed` is true, the expression is anchored to that type,
+    /// which helps with type inference.
+    fn expr(&self, path: &Path, typed: bool) -> Expr {
+        match self {
+            Fill::Trait(fill_trait, method) if typed => parse_quote! {
+                <#path as #fill_trait>::#method()
+            },
+            Fill::Trait(fill_trait, method) => parse_quote! { #fill_trait::#method() },
+            Fill::Call(func) if typed => parse_quote! {
+                ::core::convert::identity::<#path>(#func())
+            },
+            Fill::Call(func) => parse_quote! { #func() },
+            Fill::Expr(expr) => (**expr).clone(),
+            Fill::Inherent(method) => parse_quote! { #path::#method() },
+        }
+    }
+}
+
+/// A single `name = expr` entry in a `fill(...)` or `field(...)` list
+struct NamedExpr {
+    ident: Ident,
+    expr: Expr,
+}
+
+impl Parse for NamedExpr {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let ident = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let expr = input.parse()?;
+
+        Ok(NamedExpr { ident, expr })
+    }
+}
+
+/// Check if a struct expression's path looks like an enum variant rather
+/// than a plain struct; that is, if any component before the last one starts
+/// with an uppercase letter (like `Shape::Rect` or `Self::Rect`)
+fn looks_like_variant(path: &Path) -> bool {
+    let count = path.segments.len();
+
+    path.segments.iter().take(count - 1).any(|segment| {
+        segment
+            .ident
+            .to_string()
+            .trim_start_matches("r#")
+            .starts_with(|c: char| c.is_uppercase())
+    })
+}
+
+/// Get the number of tokens at the start of a macro argument that come before
+/// its expression: a `name =`, `a.b =`, or `name:` directive, followed by an
+/// optional `?` or `%` sigil
+fn macro_arg_prefix_len(arg: &[TokenTree]) -> usize {
+    let is_punct = |index: usize, c: char| {
+        matches!(arg.get(index), Some(TokenTree::Punct(punct))
+            if punct.as_char() == c && punct.spacing() == Spacing::Alone)
+    };
+
+    let mut len = 0;
+
+    if let Some(TokenTree::Ident(_)) = arg.first() {
+        let mut end = 1;
+
+        while is_punct(end, '.') && matches!(arg.get(end + 1), Some(TokenTree::Ident(_))) {
+            end += 2;
+        }
+
+        if is_punct(end, '=') || (end == 1 && is_punct(end, ':')) {
+            len = end + 1;
+        }
+    }
+
+    if is_punct(len, '?') || is_punct(len, '%') {
+        len += 1;
+    }
+
+    len
+}
+
+/// The options that pick what struct expressions are filled with. Only one
+/// of these can be given, except that `fill_method` can be combined with one
+/// of `TRAIT_FILL_OPTIONS`.
+const FILL_OPTIONS: &[&str] = &[
+    "with",
+    "from",
+    "inherent",
+    "rest_tokens",
+    "path",
+    "unqualified",
+    "fill_trait",
+    "fill_method",
+];
+
+/// The fill options that only pick the trait to fill through
+const TRAIT_FILL_OPTIONS: &[&str] = &["path", "unqualified", "fill_trait"];
+
+/// Check if two options from `FILL_OPTIONS` can be given together
+fn fill_options_combine(first: &str, second: &str) -> bool {
+    let combine = |method: &str, fill_trait: &str| {
+        method == "fill_method" && TRAIT_FILL_OPTIONS.contains(&fill_trait)
+    };
+
+    combine(first, second) || combine(second, first)
+}
+
+/// The names of all the options that can be given to `#[autodefault(...)]`
+const OPTIONS: &[&str] = &[
+    "except",
+    "only",
+    "rules",
+    "only_from",
+    "except_from",
+    "only_derive",
+    "only_fns",
+    "use",
+    "macros",
+    "ignore_unit_like",
+    "skip_generics",
+    "strict_structs",
+    "except_fns",
+    "use_binding_type",
+    "return_only",
+    "bindings_only",
+    "with",
+    "from",
+    "inherent",
+    "rest_tokens",
+    "fill",
+    "fill_only",
+    "template",
+    "field",
+    "fields_first",
+    "require_field",
+    "typed",
+    "path",
+    "unqualified",
+    "fill_trait",
+    "fill_method",
+    "const_default",
+    "checked",
+    "allow_clippy",
+    "allow_qualification",
+    "debug",
+    "dry_run",
+    "warn",
+    "summary",
+    "count_const",
+    "exhaustive",
+    "check_filters",
+    "require_effect",
+    "strip_redundant",
+    "strip",
+    "test_only",
+    "empty",
+    "recursion_limit",
+    "max_literals",
+    "max_depth",
+    "outermost",
+    "innermost",
+];
+
+/// The number of single-character insertions, deletions, or substitutions
+/// needed to turn `a` into `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+
+        for (j, &b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != b_char);
+            let insertion = current[j] + 1;
+            let deletion = previous[j + 1] + 1;
+            current.push(substitution.min(insertion).min(deletion));
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Find the known option closest to an unknown one, if any are close enough
+/// to be a likely typo
+fn suggest_option(unknown: &str) -> Option<&'static str> {
+    OPTIONS
+        .iter()
+        .map(|&option| (edit_distance(unknown, option), option))
+        .filter(|&(distance, _)| distance <= 2)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, option)| option)
+}
+
+/// Parse the argument to `path = ...`, which is either `core`, `std`, or a
+/// string containing the full path to the `Default` trait
+fn parse_default_path(input: syn::parse::ParseStream) -> syn::Result<Path> {
+    if input.peek(LitStr) {
+        let path: LitStr = input.parse()?;
+        return path.parse();
+    }
+
+    let root: Ident = input.parse()?;
+
+    match root.to_string().as_str() {
+        "core" | "std" => Ok(parse_quote! { ::#root::default::Default }),
+        "alloc" => Err(syn::Error::new(
+            root.span(),
+            "The alloc crate doesn't provide Default; use 'core', \
+            or give the path to Default as a string",
+        )),
+        _ => Err(syn::Error::new(
+            root.span(),
+            "Expected 'core', 'std', or the path to Default as a string",
+        )),
+    }
+}
+
+/// The complete set of arguments given to `#[autodefault(...)]`
+#[derive(Default)]
+struct Options {
+    rules: Rules,
+
+    /// If given, only process functions with these names, from
+    /// `only_fns(...)`
+    only_fns: Option<Vec<Ident>>,
+
+    /// Functions that shouldn't be processed, from `except_fns(...)`
+    except_fns: Vec<Ident>,
+
+    /// The names of macros whose arguments should be processed, from
+    /// `macros(...)`
+    macros: Vec<Ident>,
+
+    /// The names of unit structs, which are never filled, and whose empty
+    /// struct expressions are replaced with just the name, from
+    /// `ignore_unit_like(...)`
+    unit_like: Vec<Ident>,
+
+    /// If true, don't fill struct expressions with generic arguments
+    skip_generics: bool,
+
+    /// If true, don't fill struct expressions that look like enum variants
+    strict_structs: bool,
+
+    /// The name of a set of options from `define_filters!` to use, from
+    /// `use(name)`
+    use_filters: Option<Ident>,
+
+    /// If true, only fill structs that are defined alongside the annotated
+    /// item and that `#[derive(Default)]`
+    only_derive: bool,
+
+    /// If true, when a struct expression is used to initialize a `let`
+    /// binding with a type annotation, match against the annotated type
+    /// instead of the struct expression's own path
+    use_binding_type: bool,
+
+    /// If true, it's an error for a struct expression that would otherwise
+    /// be filled to already have a `..rest`
+    exhaustive: bool,
+
+    /// What to do with empty struct expressions
+    empty: Empty,
+
+    /// If true, only process the item in `cfg(test)` builds, and emit it
+    /// unprocessed otherwise
+    test_only: bool,
+
+    /// If true, remove `..Default::default()` from struct expressions
+    /// instead of adding it
+    strip: bool,
+
+    /// If true, it's an error for an `only` or `except` entry to not match
+    /// any struct expression
+    check_filters: bool,
+
+    /// If true, it's an error for the annotated item to not have any struct
+    /// expressions filled
+    require_effect: bool,
+
+    /// If true, remove hand-written `..Default::default()`s from struct
+    /// expressions that would be filled, so that they're filled as usual
+    strip_redundant: bool,
+
+    /// If true, only fill struct expressions that are returned from the
+    /// function, either as its tail expression or with `return`
+    return_only: bool,
+
+    /// If true, only fill struct expressions that are the initializer of a
+    /// `let` binding
+    bindings_only: bool,
+
+    /// If given, only fill struct expressions nested at most this deep in
+    /// other struct expressions, from `max_depth = N`
+    max_depth: Option<usize>,
+
+    /// If true, only fill struct expressions that aren't nested inside other
+    /// struct expressions
+    outermost: bool,
+
+    /// If true, only fill struct expressions that don't contain other struct
+    /// expressions
+    innermost: bool,
+
+    /// The expression to use as the `..rest` of filled struct expressions
+    fill: Fill,
+
+    /// If true, print the transformed item to stderr during compilation
+    debug: bool,
+
+    /// If true, report the struct expressions that would be filled to
+    /// stderr, but leave the item unchanged
+    dry_run: bool,
+
+    /// If true, emit a warning for each filled struct expression (nightly
+    /// only)
+    warn: bool,
+
+    /// If true, print a summary of the filled struct expressions to stderr
+    summary: bool,
+
+    /// The name of a `const` to define with the number of filled struct
+    /// expressions, from `count_const = NAME`
+    count_const: Option<Ident>,
+
+    /// The deepest nesting of delimited groups that will be processed, from
+    /// `recursion_limit = N`
+    recursion_limit: Option<usize>,
+
+    /// The most struct expressions that may be filled before it's an error,
+    /// from `max_literals = N`
+    max_literals: Option<usize>,
+
+    /// If true, add `#[allow(clippy::default_trait_access)]` to processed
+    /// functions
+    allow_clippy: bool,
+
+    /// If true, add `#[allow(unused_qualifications)]` to processed functions
+    allow_qualification: bool,
+
+    /// If true, assert that every type filled with `Default::default()`
+    /// actually implements `Default`, for clearer error messages
+    checked: bool,
+
+    /// The function to call as the fill in `const fn`s, from
+    /// `const_default = path::to::const_fn`
+    const_fill: Option<Path>,
+
+    /// Per-type overrides of `fill`, from `fill(Type = expr, ...)`
+    type_fills: HashMap<Ident, Expr>,
+
+    /// If true, only fill types that appear in `type_fills`
+    fill_only: bool,
+
+    /// Fields to add to filled struct expressions that don't already set
+    /// them, from `field(name = expr, ...)`
+    fields: Vec<(Ident, Expr)>,
+
+    /// Struct expressions whose fields are added to struct expressions of
+    /// the same type, instead of a `..rest`, from `template(...)`
+    templates: Vec<ExprStruct>,
+
+    /// If true, put the fields from `field(...)` before the existing fields,
+    /// instead of after them
+    fields_first: bool,
+
+    /// Fields that must be given explicitly in any struct expression that's
+    /// filled, from `require_field(...)`
+    required_fields: Vec<Ident>,
+
+    /// If true, anchor the inserted `Default::default()` to the struct
+    /// expression's type, as `<Type as Default>::default()`
+    typed: bool,
+}
+
+impl Options {
+    /// Check if the function with this name should be processed, according
+    /// to `only_fns` and `except_fns`
+    fn selects_fn(&self, name: &Ident) -> bool {
+        let only = match &self.only_fns {
+            Some(only_fns) => only_fns.contains(name),
+            None => true,
+        };
+
+        only && !self.except_fns.contains(name)
+    }
+
+    /// Parse more options on top of these ones. Later options override
+    /// earlier ones, and `only` and `except` lists are combined.
+    fn parse_more(&mut self, input: syn::parse::ParseStream) -> syn::Result<()> {
+        let options = self;
+
+        // True if the current rules came from `config(...)`, in which case
+        // they can be overridden
+        let mut inherited_rules = false;
+
+        // The fill options given so far, which mostly can't be combined. Ones
+        // from `config(...)` are parsed separately, and so can be overridden.
+        let mut fill_options: Vec<Ident> = Vec::new();
+
+        while !input.is_empty() {
+            // `use` is a keyword, so this has to accept keywords too
+            let option: Ident = input.call(Ident::parse_any)?;
+            let name = option.to_string();
+
+            if FILL_OPTIONS.contains(&name.as_str()) {
+                let conflict = fill_options
+                    .iter()
+                    .find(|previous| !fill_options_combine(&previous.to_string(), &name));
+
+                if let Some(previous) = conflict {
+                    return Err(syn::Error::new(
+                        option.span(),
+                        format!("'{}' can't be combined with '{}'", option, previous),
+                    ));
+                }
+
+                fill_options.push(option.clone());
+            }
+
+            match option.to_string().as_str() {
+                "only" | "except" | "only_from" | "except_from" | "rules" => {
+                    let rules = match option.to_string().as_str() {
+                        "rules" => parse_clauses(input)?,
+                        "only_from" => make_rules(true, parse_filter_from(input)?),
+                        "except_from" => make_rules(false, parse_filter_from(input)?),
+                        kind => make_rules(kind == "only", parse_filter(input)?),
+                    };
+
+                    if mem::take(&mut inherited_rules) {
+                        options.rules = rules;
+                    } else {
+                        options.rules.merge(rules, option.span())?;
+                    }
+                }
+                // Inserted by `#[autodefault_config(...)]` and `use(name)`,
+                // ahead of the function's own options, which are applied on
+                // top. Both can be given, in which case they're combined.
+                "config" => {
+                    let content;
+                    let _parens = parenthesized!(content in input);
+
+                    options.parse_more(&content)?;
+                    inherited_rules = true;
+
+                    if let Some(name) = &options.use_filters {
+                        return Err(syn::Error::new(
+                            name.span(),
+                            "'use' can't be given in config",
+                        ));
+                    }
+                }
+                "only_fns" | "except_fns" => {
+                    let content;
+                    let _parens = parenthesized!(content in input);
+
+                    let names: Punctuated<Ident, Token![,]> =
+                        Punctuated::parse_terminated(&content)?;
+                    let names = names.into_iter();
+
+                    if option == "only_fns" {
+                        options.only_fns.get_or_insert_with(Vec::new).extend(names);
+                    } else {
+                        options.except_fns.extend(names);
+                    }
+                }
+                "macros" => {
+                    let content;
+                    let _parens = parenthesized!(content in input);
+
+                    let names: Punctuated<Ident, Token![,]> =
+                        Punctuated::parse_terminated(&content)?;
+
+                    options.macros.extend(names);
+                }
+                "ignore_unit_like" => {
+                    let content;
+                    let _parens = parenthesized!(content in input);
+
+                    let names: Punctuated<Ident, Token![,]> =
+                        Punctuated::parse_terminated(&content)?;
+
+                    options.unit_like.extend(names);
+                }
+                "skip_generics" => options.skip_generics = true,
+                "strict_structs" => options.strict_structs = true,
+                "fields_first" => options.fields_first = true,
+                "require_field" => {
+                    let content;
+                    let _parens = parenthesized!(content in input);
+
+                    let names: Punctuated<Ident, Token![,]> =
+                        Punctuated::parse_terminated(&content)?;
+
+                    options.required_fields.extend(names);
+                }
+                "only_derive" => options.only_derive = true,
+                "use_binding_type" => options.use_binding_type = true,
+                "return_only" => options.return_only = true,
+                "bindings_only" => options.bindings_only = true,
+                "use" => {
+                    if options.use_filters.is_some() {
+                        return Err(syn::Error::new(
+                            option.span(),
+                            "Only one 'use' may be given",
+                        ));
+                    }
+
+                    let content;
+                    let _parens = parenthesized!(content in input);
+                    options.use_filters = Some(content.parse()?);
+                }
+                "with" => {
+                    input.parse::<Token![=]>()?;
+                    options.fill = Fill::Call(input.parse()?);
+                }
+                "from" => {
+                    input.parse::<Token![=]>()?;
+                    let func: Ident = input.parse()?;
+                    options.fill = Fill::Call(func.into());
+                }
+                "inherent" => {
+                    input.parse::<Token![=]>()?;
+                    options.fill = Fill::Inherent(input.parse()?);
+                }
+                "rest_tokens" => {
+                    let content;
+                    let _parens = parenthesized!(content in input);
+                    let tokens: LitStr = content.parse()?;
+
+                    let expr = tokens.parse_with(|input: syn::parse::ParseStream| {
+                        input.parse::<Option<Token![..]>>()?;
+                        input.parse::<Expr>()
+                    })?;
+
+                    options.fill = Fill::Expr(Box::new(expr));
+                }
+                "checked" => options.checked = true,
+                "allow_clippy" => options.allow_clippy = true,
+                "allow_qualification" => options.allow_qualification = true,
+                "debug" => options.debug = true,
+                "dry_run" => options.dry_run = true,
+                "warn" => options.warn = true,
+                "summary" => options.summary = true,
+                "count_const" => {
+                    input.parse::<Token![=]>()?;
+                    options.count_const = Some(input.parse()?);
+                }
+                "exhaustive" => options.exhaustive = true,
+                "check_filters" => options.check_filters = true,
+                "require_effect" => options.require_effect = true,
+                "strip_redundant" => options.strip_redundant = true,
+                "strip" => options.strip = true,
+                "test_only" => options.test_only = true,
+                "empty" => {
+                    input.parse::<Token![=]>()?;
+                    options.empty = input.parse()?;
+                }
+                "max_depth" => {
+                    input.parse::<Token![=]>()?;
+                    let depth: LitInt = input.parse()?;
+                    let value = depth.base10_parse()?;
+
+                    if value == 0 {
+                        return Err(syn::Error::new(
+                            depth.span(),
+                            "max_depth must be at least 1",
+                        ));
+                    }
+
+                    options.max_depth = Some(value);
+                }
+                "recursion_limit" => {
+                    input.parse::<Token![=]>()?;
+                    let limit: LitInt = input.parse()?;
+                    options.recursion_limit = Some(limit.base10_parse()?);
+                }
+                "max_literals" => {
+                    input.parse::<Token![=]>()?;
+                    let limit: LitInt = input.parse()?;
+                    options.max_literals = Some(limit.base10_parse()?);
+                }
+                "outermost" => options.outermost = true,
+                "innermost" => options.innermost = true,
+                "const_default" => {
+                    input.parse::<Token![=]>()?;
+                    options.const_fill = Some(input.parse()?);
+                }
+                "fill" => {
+                    let content;
+                    let _parens = parenthesized!(content in input);
+
+                    let fills: Punctuated<NamedExpr, Token![,]> =
+                        Punctuated::parse_terminated(&content)?;
+
+                    options
+                        .type_fills
+                        .extend(fills.into_iter().map(|fill| (fill.ident, fill.expr)));
+                }
+                "fill_only" => options.fill_only = true,
+                "template" => {
+                    let content;
+                    let _parens = parenthesized!(content in input);
+
+                    let templates: Punctuated<ExprStruct, Token![,]> =
+                        Punctuated::parse_terminated(&content)?;
+
+                    for template in templates {
+                        if let Some(rest) = &template.rest {
+                            return Err(syn::Error::new_spanned(
+                                rest,
+                                "templates can't have a ..rest",
+                            ));
+                        }
+
+                        options.templates.push(template);
+                    }
+                }
+                "typed" => options.typed = true,
+                "unqualified" => options.fill.set_trait(parse_quote! { Default }),
+                "path" => {
+                    input.parse::<Token![=]>()?;
+                    options.fill.set_trait(parse_default_path(input)?);
+                }
+                "fill_trait" => {
+                    input.parse::<Token![=]>()?;
+                    options.fill.set_trait(input.parse()?);
+                }
+                "fill_method" => {
+                    input.parse::<Token![=]>()?;
+                    options.fill.set_method(input.parse()?);
+                }
+                "field" => {
+                    let content;
+                    let _parens = parenthesized!(content in input);
+
+                    let fields: Punctuated<NamedExpr, Token![,]> =
+                        Punctuated::parse_terminated(&content)?;
+
+                    // A field that's given again (for instance, on top of a
+                    // `config(...)`) replaces the earlier one, in its place
+                    for field in fields {
+                        match options
+                            .fields
+                            .iter_mut()
+                            .find(|(name, _)| *name == field.ident)
+                        {
+                            Some(existing) => existing.1 = field.expr,
+                            None => options.fields.push((field.ident, field.expr)),
+                        }
+                    }
+                }
+                unknown => {
+                    let message = match suggest_option(unknown) {
+                        Some(suggestion) => format!(
+                            "Unknown option '{}'; did you mean '{}'?",
+                            unknown, suggestion
+                        ),
+                        None => {
+                            let expected: Vec<String> =
+                                OPTIONS.iter().map(|name| format!("'{}'", name)).collect();
+
+                            format!(
+                                "Unknown option '{}'; expected one of {}",
+                                unknown,
+                                expected.join(", ")
+                            )
+                        }
+                    };
+
+                    return Err(syn::Error::new(option.span(), message));
+                }
+            }
+
+            if input.is_empty() {
+                break;
+            }
+
+            input.parse::<Token![,]>()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Parse for Options {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let mut options = Options::default();
+        options.parse_more(input)?;
+
+        if options.strip && options.require_effect {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "'strip' can't be combined with 'require_effect', since it doesn't \
+                fill anything",
+            ));
+        }
+
+        Ok(options)
+    }
+}
+
+/// Check if a set of attributes includes a `#[derive(Default)]`
+fn derives_default(attrs: &[Attribute]) -> bool {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("derive"))
+        .filter_map(|attr| {
+            attr.parse_args_with(Punctuated::<Path, Token![,]>::parse_terminated)
+                .ok()
+        })
+        .flatten()
+        .any(|path| match path.segments.last() {
+            Some(segment) => segment.ident == "Default",
+            None => false,
+        })
+}
+
+/// Get the names of all the structs in a list of items that
+/// `#[derive(Default)]`
+fn derived_defaults<'a>(items: impl IntoIterator<Item = &'a Item>) -> HashSet<Ident> {
+    items
+        .into_iter()
+        .filter_map(|item| match item {
+            Item::Struct(item) if derives_default(&item.attrs) => Some(item.ident.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Check if an attribute is `#[autodefault::skip]` (or a plain `#[skip]`,
+/// if it was imported)
+fn is_skip_attribute(attr: &Attribute) -> bool {
+    let segments: Vec<String> = attr
+        .path
+        .segments
+        .iter()
+        .map(|segment| segment.ident.to_string())
+        .collect();
+
+    attr.tokens.is_empty() && (segments == ["autodefault", "skip"] || segments == ["skip"])
+}
+
+/// Get the names of all the structs among these items that are tagged with
+/// `#[autodefault::skip]`
+fn skipped_structs<'a>(items: impl IntoIterator<Item = &'a Item>) -> HashSet<Ident> {
+    items
+        .into_iter()
+        .filter_map(|item| match item {
+            Item::Struct(item) if item.attrs.iter().any(is_skip_attribute) => {
+                Some(item.ident.clone())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Check if the expressions directly inside of this one are in return
+/// position when it is. Expressions that build their value out of the ones
+/// inside them (like struct, tuple, and array expressions, and calls to enum
+/// variants and tuple structs like `Ok(...)`) and ones that handle return
+/// position themselves (like blocks and `if`) do; others, like function
+/// calls, don't.
+fn passes_return(expr: &Expr) -> bool {
+    if let Expr::Call(call) = expr {
+        return match ungroup(&call.func) {
+            Expr::Path(func) if func.qself.is_none() => {
+                let name = func.path.segments.last().unwrap().ident.to_string();
+                name.starts_with(char::is_uppercase)
+            }
+            _ => false,
+        };
+    }
+
+    matches!(
+        expr,
+        Expr::Struct(_)
+            | Expr::Tuple(_)
+            | Expr::Array(_)
+            | Expr::Repeat(_)
+            | Expr::Paren(_)
+            | Expr::Group(_)
+            | Expr::Block(_)
+            | Expr::Unsafe(_)
+            | Expr::If(_)
+            | Expr::Match(_)
+            | Expr::Return(_)
+            | Expr::Closure(_)
+            | Expr::Async(_)
+    )
+}
+
+/// Check if a struct expression explicitly sets the named field
+fn has_field(struct_expr: &ExprStruct, name: &Ident) -> bool {
+    struct_expr.fields.iter().any(|field| match &field.member {
+        Member::Named(member) => member == name,
+        Member::Unnamed(_) => false,
+    })
+}
+
+/// Look through the invisible groups that `macro_rules!` puts around
+/// interpolated fragments, like `$e:expr`
+fn ungroup(mut expr: &Expr) -> &Expr {
+    while let Expr::Group(group) = expr {
+        expr = &group.expr;
+    }
+
+    expr
+}
+
+/// Check if an expression is a call to `Default::default()`, with any
+/// qualification (like `::core::default::Default::default()` or
+/// `<Foo as Default>::default()`)
+fn is_default_call(expr: &Expr) -> bool {
+    let call = match ungroup(expr) {
+        Expr::Call(call) if call.args.is_empty() => call,
+        _ => return false,
+    };
+
+    let path = match &*call.func {
+        Expr::Path(path) => &path.path,
+        _ => return false,
+    };
+
+    let mut segments = path.segments.iter().rev();
+
+    matches!(
+        (segments.next(), segments.next()),
+        (Some(method), Some(default_trait))
+            if method.ident == "default" && default_trait.ident == "Default"
+    )
+}
+
+/// Check if an attribute is the `#[autodefault(skip)]` marker, which
+/// disables filling for a single struct expression
+fn is_skip_marker(attr: &Attribute) -> bool {
+    attr.path.is_ident("autodefault")
+        && match attr.parse_args::<Ident>() {
+            Ok(arg) => arg == "skip",
+            Err(_) => false,
+        }
+}
+
+/// Remove any `#[autodefault(skip)]` markers from a list of attributes,
+/// returning true if there were any
+fn take_skip_marker(attrs: &mut Vec<Attribute>) -> bool {
+    let count = attrs.len();
+    attrs.retain(|attr| !is_skip_marker(attr));
+    attrs.len() != count
+}
+
+/// Add an `#[allow(lint)]` to a list of attributes, unless there's already one
+/// present
+fn add_allow(attrs: &mut Vec<Attribute>, lint: Path) {
+    let lint_name = lint.to_token_stream().to_string();
+
+    let present = attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("allow"))
+        .filter_map(|attr| {
+            attr.parse_args_with(Punctuated::<Path, Token![,]>::parse_terminated)
+                .ok()
+        })
+        .flatten()
+        .any(|allowed| allowed.to_token_stream().to_string() == lint_name);
+
+    if !present {
+        attrs.insert(0, parse_quote! { #[allow(#lint)] });
+    }
+}
+
+struct AutodefaultVisitor {
+    options: Options,
+
+    /// The structs tagged with `#[autodefault::skip]` among the items being
+    /// processed
+    skipped: HashSet<Ident>,
+
+    /// When `only_derive` is in effect, this is the set of struct names that
+    /// are known to `#[derive(Default)]`
+    derived: HashSet<Ident>,
+
+    /// When `use_binding_type` is in effect, this is the annotated type of
+    /// the `let` binding whose initializer is about to be visited
+    binding_type: Option<Path>,
+
+    /// The path of the type of the impl block currently being processed, if
+    /// any
+    self_type: Option<Path>,
+
+    /// The type parameters of the function currently being processed (and
+    /// of its `impl` block), which are never filled
+    type_params: HashSet<Ident>,
+
+    /// The number of struct expressions currently being visited, including
+    /// the innermost one
+    depth: usize,
+
+    /// True if a struct expression has finished being visited since this was
+    /// last reset, used to detect leaf struct expressions for `innermost`
+    saw_struct: bool,
+
+    /// True if the expression currently being visited is in return position
+    /// (the tail expression of the function, or the operand of a `return`,
+    /// or a field of a struct expression in return position, and so on)
+    in_return: bool,
+
+    /// True if the expression currently being visited is anywhere inside of a
+    /// returned expression, even if it isn't returned itself (like the
+    /// argument of a returned function call)
+    in_returned: bool,
+
+    /// True if the struct expression about to be visited is the initializer
+    /// of a `let` binding
+    in_binding: bool,
+
+    /// True if the function currently being processed returns an
+    /// `impl Trait`, in which case closures and async blocks in its return
+    /// value are considered returned too
+    returns_impl: bool,
+
+    /// If the expression currently being visited is in a const context (a
+    /// `const fn`, or an array length), this is the span of the `const`
+    /// keyword or the array length
+    const_fn: Option<Span>,
+
+    /// Errors encountered while processing, which are emitted alongside the
+    /// processed item
+    errors: Vec<syn::Error>,
+
+    /// The name and location of every struct expression that was filled,
+    /// along with what was inserted into it (its `..rest`, or the fields from
+    /// `field(...)` or a template)
+    filled: Vec<(String, Span, String)>,
+
+    /// When `check_filters` is in effect, this is the path of every struct
+    /// expression that was checked against the `only` or `except` rules
+    seen_paths: Vec<Path>,
+
+    /// When `checked` is in effect, the paths (as written) of the struct
+    /// expressions in the current function that already assert that their
+    /// type implements the fill trait
+    checked_types: HashSet<String>,
+}
+
+impl AutodefaultVisitor {
+    fn new(options: Options) -> Self {
+        AutodefaultVisitor {
+            options,
+            skipped: HashSet::new(),
+            self_type: None,
+            type_params: HashSet::new(),
+            derived: HashSet::new(),
+            binding_type: None,
+            depth: 0,
+            saw_struct: false,
+            in_return: false,
+            in_returned: false,
+            in_binding: false,
+            returns_impl: false,
+            const_fn: None,
+            errors: Vec::new(),
+            filled: Vec::new(),
+            seen_paths: Vec::new(),
+            checked_types: HashSet::new(),
+        }
+    }
+
+    /// Check if a struct expression with this path should have
+    /// `..Default::default()` added to it
+    fn should_fill(&self, path: &Path) -> bool {
+        let last_segment = path.segments.last().unwrap();
+        let struct_ident = &last_segment.ident;
+
+        // In an impl block, `Self` in a filter stands for the impl's type
+        let matches = |filter: &Filter| {
+            filter.matches(path)
+                || matches!(&self.self_type, Some(self_type)
+                    if self_type.segments.last().unwrap().ident == *struct_ident
+                        && filter.names.iter().any(|name| name == "Self"))
+        };
+
+        match &self.options.rules {
+            Rules::Only(allow_list) if !matches(allow_list) => return false,
+            Rules::Except(deny_list) if matches(deny_list) => return false,
+            // The last matching clause wins. If none match, a list starting
+            // with `except` fills everything else, and one starting with `only`
+            // fills nothing else.
+            Rules::Clauses(clauses) => {
+                let fill = match clauses.iter().rev().find(|clause| matches(&clause.filter)) {
+                    Some(clause) => clause.fill,
+                    None => !matches!(clauses.first(), Some(clause) if clause.fill),
+                };
+
+                if !fill {
+                    return false;
+                }
+            }
+            _ => {}
+        }
+
+        if self.options.unit_like.contains(struct_ident) {
+            return false;
+        }
+
+        if self.options.only_derive && !self.derived.contains(struct_ident) {
+            return false;
+        }
+
+        if self.options.skip_generics && !last_segment.arguments.is_empty() {
+            return false;
+        }
+
+        if self.options.strict_structs && looks_like_variant(path) {
+            return false;
+        }
+
+        if self.options.fill_only && !self.options.type_fills.contains_key(struct_ident) {
+            return false;
+        }
+
+        !self.skipped.contains(struct_ident)
+    }
+
+    /// Get the expression to use as the `..rest` for a struct expression.
+    /// `path` is the path used for matching, and `struct_path` is the path
+    /// of the struct expression itself.
+    fn fill_expr(&mut self, path: &Path, struct_path: &Path) -> Expr {
+        let struct_ident = &path.segments.last().unwrap().ident;
+
+        if let Some(expr) = self.options.type_fills.get(struct_ident) {
+            return expr.clone();
+        }
+
+        if let Some(const_span) = self.const_fn {
+            match &self.options.const_fill {
+                Some(func) => return parse_quote! { #func() },
+                None if matches!(self.options.fill, Fill::Trait(..)) => {
+                    // Only report this once per function
+                    self.const_fn = None;
+                    self.errors.push(syn::Error::new(
+                        const_span,
+                        "Default::default() can't be called in a const fn or other \
+                        const context; use const_default = path::to::const_fn to give \
+                        a const fill",
+                    ));
+                }
+                None => {}
+            }
+        }
+
+        // With `checked`, the first fill of each type in a function goes
+        // through a function that requires the fill trait, so that a missing
+        // impl is reported as such. Without `typed`, its type is inferred from
+        // the struct expression, which works even for generic types written
+        // without their generic arguments.
+        if let Fill::Trait(fill_trait, method) = &self.options.fill {
+            if self.options.checked
+                && self
+                    .checked_types
+                    .insert(struct_path.to_token_stream().to_string())
+            {
+                let call = match self.options.typed {
+                    true => quote! { assert_default::<#struct_path>() },
+                    false => quote! { assert_default() },
+                };
+
+                return parse_quote! {{
+                    fn assert_default<T: #fill_trait>() -> T {
+                        <T as #fill_trait>::#method()
+                    }
+
+                    #call
+                }};
+            }
+        }
+
+        self.options.fill.expr(struct_path, self.options.typed)
+    }
+
+    fn process_fn(&mut self, item_fn: &mut ItemFn) {
+        self.type_params = HashSet::new();
+        self.process_fn_parts(&mut item_fn.attrs, &item_fn.sig, &mut item_fn.block);
+    }
+
+    /// Report an error for each field from `require_field` that this struct
+    /// expression doesn't set
+    fn check_required_fields(&mut self, struct_expr: &ExprStruct) {
+        for name in &self.options.required_fields {
+            if !has_field(struct_expr, name) {
+                self.errors.push(syn::Error::new_spanned(
+                    struct_expr,
+                    format!("The field '{}' must be set explicitly", name),
+                ));
+            }
+        }
+    }
+
+    /// Record that the struct expression with this path was filled, with a
+    /// description of what was inserted into it
+    fn record_fill(&mut self, path: &Path, inserted: String) {
+        let struct_ident = &path.segments.last().unwrap().ident;
+        self.filled
+            .push((struct_ident.to_string(), struct_ident.span(), inserted));
+    }
+
+    /// Leave return position, for a part of an expression that's never
+    /// returned (like the condition of an `if`), returning the previous state
+    fn leave_return(&mut self) -> (bool, bool) {
+        (
+            mem::replace(&mut self.in_return, false),
+            mem::replace(&mut self.in_returned, false),
+        )
+    }
+
+    /// Enter the body of a closure or `async` block, returning the previous
+    /// state. These are only in return position if they're part of the
+    /// returned value of a function that returns an `impl Trait`.
+    fn enter_closure(&mut self) -> (bool, bool) {
+        let returned = self.in_returned && self.returns_impl;
+
+        (
+            mem::replace(&mut self.in_return, returned),
+            mem::replace(&mut self.in_returned, returned),
+        )
+    }
+
+    /// Visit a const generic argument, like the `{ Bar {} }` in
+    /// `Foo::<{ Bar {} }>`. Struct expressions there are only filled when
+    /// `const_default` is given; otherwise they're left alone, rather than
+    /// inserting a `Default::default()` that can't be called there.
+    fn visit_const_argument(&mut self, expr: &mut Expr) {
+        if self.options.const_fill.is_some() {
+            let const_fn = self.const_fn.replace(expr.span());
+            self.visit_expr_mut(expr);
+            self.const_fn = const_fn;
+        }
+    }
+
+    /// Process a function, given its pieces, so that both free functions and
+    /// methods can be handled
+    fn process_fn_parts(&mut self, attrs: &mut Vec<Attribute>, sig: &Signature, block: &mut Block) {
+        if !self.options.selects_fn(&sig.ident) {
+            StripMarkers { in_fn: false }.visit_block_mut(block);
+            return;
+        }
+
+        self.type_params
+            .extend(sig.generics.type_params().map(|param| param.ident.clone()));
+
+        let local_items = block.stmts.iter().filter_map(|stmt| match stmt {
+            Stmt::Item(item) => Some(item),
+            _ => None,
+        });
+
+        if self.options.only_derive {
+            self.derived.extend(derived_defaults(local_items.clone()));
+        }
+
+        // Structs skipped inside this function only apply to it
+        let outer_skipped = self.skipped.clone();
+        self.skipped.extend(skipped_structs(local_items));
+
+        self.const_fn = sig.constness.map(|token| token.span);
+        self.checked_types = HashSet::new();
+
+        if self.options.allow_clippy {
+            add_allow(attrs, parse_quote! { clippy::default_trait_access });
+        }
+
+        if self.options.allow_qualification {
+            add_allow(attrs, parse_quote! { unused_qualifications });
+        }
+
+        // The function body itself is in return position
+        self.in_return = true;
+        self.in_returned = true;
+        self.returns_impl =
+            matches!(&sig.output, ReturnType::Type(_, ty) if matches!(**ty, Type::ImplTrait(_)));
+        self.visit_block_mut(block);
+
+        self.skipped = outer_skipped;
+    }
+
+    /// Process a single `let` statement, for `#[autodefault]` applied
+    /// directly to one (which requires nightly)
+    fn process_local(&mut self, local: &mut Local) {
+        self.visit_local_mut(local);
+    }
+
+    /// Process the arguments of a macro from `macros(...)`, on a best-effort
+    /// basis. The arguments are split on commas, and each one is processed as
+    /// an expression after skipping a leading `name =` or `name:` and a `?`
+    /// or `%` sigil (as in `tracing::info!(value = ?Foo {})`). If any
+    /// argument isn't an expression, this returns `None`, and the macro is
+    /// left alone.
+    fn process_macro_args(&mut self, tokens: TokenStream2) -> Option<TokenStream2> {
+        let mut args = vec![Vec::new()];
+        let mut commas = Vec::new();
+
+        for token in tokens {
+            match token {
+                TokenTree::Punct(ref punct) if punct.as_char() == ',' => {
+                    commas.push(token);
+                    args.push(Vec::new());
+                }
+                token => args.last_mut().unwrap().push(token),
+            }
+        }
+
+        let mut output = TokenStream2::new();
+        let mut commas = commas.into_iter();
+
+        for arg in args {
+            let prefix_len = macro_arg_prefix_len(&arg);
+            let mut arg = arg.into_iter();
+
+            output.extend(arg.by_ref().take(prefix_len));
+
+            let rest: TokenStream2 = arg.collect();
+
+            if !rest.is_empty() {
+                let mut expr: Expr = parse2(rest).ok()?;
+                self.visit_expr_mut(&mut expr);
+                expr.to_tokens(&mut output);
+            }
+
+            output.extend(commas.next());
+        }
+
+        Some(output)
+    }
+
+    /// Remove an `#[autodefault(...)]` attribute that overrides the rules for
+    /// a block or statement, and return the rules it gives
+    fn take_rules_override(&mut self, attrs: &mut Vec<Attribute>) -> Option<Rules> {
+        let position = attrs
+            .iter()
+            .position(|attr| attr.path.is_ident("autodefault") && !is_skip_marker(attr))?;
+
+        let attr = attrs.remove(position);
+
+        if attr.tokens.is_empty() {
+            return Some(Rules::All);
+        }
+
+        match attr.parse_args_with(parse_override_rules) {
+            Ok(rules) => Some(rules),
+            Err(err) => {
+                self.errors.push(err);
+                None
+            }
+        }
+    }
+
+    fn process_impl(&mut self, item_impl: &mut ItemImpl) {
+        self.self_type = match &*item_impl.self_ty {
+            Type::Path(ty) if ty.qself.is_none() => Some(ty.path.clone()),
+            _ => None,
+        };
+
+        let impl_params: HashSet<Ident> = item_impl
+            .generics
+            .type_params()
+            .map(|param| param.ident.clone())
+            .collect();
+
+        let outer_derived = self.derived.clone();
+
+        for item in &mut item_impl.items {
+            if let ImplItem::Method(method) = item {
+                self.derived = outer_derived.clone();
+                self.type_params = impl_params.clone();
+                self.process_fn_parts(&mut method.attrs, &method.sig, &mut method.block);
+            }
+        }
+
+        self.self_type = None;
+    }
+
+    fn process_trait(&mut self, item_trait: &mut ItemTrait) {
+        self.self_type = None;
+
+        let trait_params: HashSet<Ident> = item_trait
+            .generics
+            .type_params()
+            .map(|param| param.ident.clone())
+            .collect();
+
+        let outer_derived = self.derived.clone();
+
+        // Only methods with a default body have anything to process
+        for item in &mut item_trait.items {
+            if let TraitItem::Method(TraitItemMethod {
+                attrs,
+                sig,
+                default: Some(block),
+                ..
+            }) = item
+            {
+                self.derived = outer_derived.clone();
+                self.type_params = trait_params.clone();
+                self.process_fn_parts(attrs, sig, block);
+            }
+        }
+    }
+
+    /// Process any item that `#[autodefault]` can be applied to
+    fn process_item(&mut self, item: &mut Item) -> syn::Result<()> {
+        match item {
+            Item::Fn(item_fn) => self.process_fn(item_fn),
+            Item::Mod(item_mod) => return self.process_mod(item_mod),
+            Item::Impl(item_impl) => self.process_impl(item_impl),
+            Item::Trait(item_trait) => self.process_trait(item_trait),
+            item => {
+                let kind = match item {
+                    Item::Struct(_) => "a struct",
+                    Item::Enum(_) => "an enum",
+                    Item::Union(_) => "a union",
+                    Item::ForeignMod(_) => "an extern block, whose functions have no bodies",
+                    Item::Const(_) | Item::Static(_) => "a const or static",
+                    Item::Type(_) | Item::TraitAlias(_) => "a type alias",
+                    Item::Use(_) | Item::ExternCrate(_) => "an import",
+                    // This can happen when another attribute macro above
+                    // #[autodefault] turns the function into something else
+                    _ => "this item",
+                };
+
+                return Err(syn::Error::new_spanned(
+                    item,
+                    format!(
+                        "#[autodefault] can't be applied to {}; it can only be applied \
+                        to functions, modules, impl blocks, traits, and let statements. \
+                        If it's combined with other attribute macros, put it closest to \
+                        the item",
+                        kind
+                    ),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn process_mod(&mut self, item_mod: &mut ItemMod) -> syn::Result<()> {
+        let items = match &mut item_mod.content {
+            Some((_, items)) => items,
+            None => {
+                return Err(syn::Error::new_spanned(
+                    &item_mod,
+                    "#[autodefault] can only be applied to inline modules",
+                ))
+            }
+        };
+
+        let module_derived = match self.options.only_derive {
+            true => derived_defaults(items.iter()),
+            false => HashSet::new(),
+        };
+
+        self.skipped = skipped_structs(items.iter());
+
+        for item in items {
+            if let Item::Fn(_) | Item::Impl(_) | Item::Trait(_) = item {
+                self.derived = module_derived.clone();
+                self.process_item(item)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl VisitMut for AutodefaultVisitor {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        let skip = matches!(expr, Expr::Struct(struct_expr)
+            if struct_expr.attrs.iter().any(is_skip_marker));
+
+        // Only some expressions pass their return position on to the
+        // expressions inside them. For instance, the fields of a returned
+        // struct expression are returned, but the arguments of a returned
+        // function call aren't.
+        let in_return = self.in_return;
+        self.in_return = in_return && passes_return(expr);
+        visit_expr_mut(self, expr);
+        self.in_return = in_return;
+
+        // `Marker {}` becomes `Marker` for unit structs in `ignore_unit_like`
+        if let Expr::Struct(struct_expr) = expr {
+            let name = &struct_expr.path.segments.last().unwrap().ident;
+
+            if !skip
+                && struct_expr.fields.is_empty()
+                && struct_expr.rest.is_none()
+                && struct_expr.attrs.is_empty()
+                && self.options.unit_like.contains(name)
+            {
+                *expr = Expr::Path(ExprPath {
+                    attrs: Vec::new(),
+                    qself: None,
+                    path: struct_expr.path.clone(),
+                });
+            }
+        }
+    }
+
+    fn visit_expr_struct_mut(&mut self, struct_expr: &mut ExprStruct) {
+        // This must be taken before visiting any nested struct expressions,
+        // so that it's only applied to the struct expression it was set for
+        let binding_type = self.binding_type.take();
+        let in_binding = mem::take(&mut self.in_binding);
+        let skip = take_skip_marker(&mut struct_expr.attrs);
+
+        self.depth += 1;
+        let depth = self.depth;
+        self.saw_struct = false;
+        visit_expr_struct_mut(self, struct_expr);
+        let has_nested = mem::replace(&mut self.saw_struct, true);
+        self.depth -= 1;
+
+        if skip {
+            return;
+        }
+
+        if let Some(ident) = struct_expr.path.get_ident() {
+            if self.type_params.contains(ident) {
+                if self.options.warn {
+                    emit_warning(
+                        ident.span(),
+                        &format!(
+                            "autodefault: skipped `{}`, since it looks like a generic parameter",
+                            ident
+                        ),
+                    );
+                }
+
+                return;
+            }
+        }
+
+        if matches!(self.options.max_depth, Some(max_depth) if depth > max_depth) {
+            return;
+        }
+
+        if self.options.outermost && depth > 1 {
+            return;
+        }
+
+        if self.options.innermost && has_nested {
+            return;
+        }
+
+        if self.options.return_only && !self.in_return {
+            return;
+        }
+
+        if self.options.bindings_only && !in_binding {
+            return;
+        }
+
+        let path = binding_type.as_ref().unwrap_or(&struct_expr.path);
+
+        // In an impl block, `Self { .. }` is matched as the impl's type
+        let self_path = match path.is_ident("Self") {
+            true => self.self_type.clone(),
+            false => None,
+        };
+        let path = self_path.as_ref().unwrap_or(path);
+
+        if self.options.check_filters {
+            self.seen_paths.push(path.clone());
+
+            // This also counts as a use of `Self` in a filter
+            if matches!(&self.self_type, Some(self_type)
+                if self_type.segments.last().unwrap().ident == path.segments.last().unwrap().ident)
+            {
+                self.seen_paths.push(parse_quote! { Self });
+            }
+        }
+
+        if !self.should_fill(path) {
+            return;
+        }
+
+        if self.options.strip {
+            if matches!(&struct_expr.rest, Some(rest) if is_default_call(rest)) {
+                struct_expr.dot2_token = None;
+                struct_expr.rest = None;
+
+                // Remove the comma that separated the fields from the rest
+                if let Some(last) = struct_expr.fields.pop() {
+                    struct_expr.fields.push_value(last.into_value());
+                }
+            }
+
+            return;
+        }
+
+        if struct_expr.fields.is_empty() && struct_expr.rest.is_none() {
+            match self.options.empty {
+                Empty::Fill => {}
+                Empty::Skip => return,
+                Empty::Error => {
+                    self.errors.push(syn::Error::new_spanned(
+                        &*struct_expr,
+                        format!(
+                            "empty struct expression; use {}::default() instead",
+                            struct_expr
+                                .path
+                                .to_token_stream()
+                                .to_string()
+                                .replace(' ', "")
+                        ),
+                    ));
+
+                    return;
+                }
+            }
+        }
+
+        // Enum variants can't have a `..rest`, so ones that are explicitly
+        // listed in `only` only get the fields from `field(...)`
+        let variant = looks_like_variant(&struct_expr.path)
+            && match &self.options.rules {
+                Rules::Only(filter) => filter.lists_path(&struct_expr.path),
+                Rules::Clauses(clauses) => clauses
+                    .iter()
+                    .any(|clause| clause.fill && clause.filter.lists_path(&struct_expr.path)),
+                _ => false,
+            };
+
+        if self.options.strip_redundant
+            && matches!(&struct_expr.rest, Some(rest) if is_default_call(rest))
+        {
+            struct_expr.dot2_token = None;
+            struct_expr.rest = None;
+        }
+
+        if self.options.exhaustive && struct_expr.rest.is_some() {
+            self.errors.push(syn::Error::new_spanned(
+                &*struct_expr,
+                "autodefault did nothing here, since this struct expression \
+                already has a ..rest",
+            ));
+        }
+
+        // Give the inserted tokens the span of the struct expression, so that
+        // errors (like a missing `Default` impl) point at it rather than at
+        // the attribute
+        let span = struct_expr.path.span();
+
+        // Add any per-field overrides that aren't already present
+        let mut added: Punctuated<FieldValue, Token![,]> = Punctuated::new();
+
+        for (name, expr) in &self.options.fields {
+            if !has_field(struct_expr, name) {
+                if !added.empty_or_trailing() {
+                    added.push_punct(Token![,](span));
+                }
+
+                added.push_value(parse_quote! { #name: #expr });
+            }
+        }
+
+        // Enum variants only get these fields, so they count as filled if
+        // there are any
+        if variant && !added.is_empty() {
+            let fields = added.to_token_stream().to_string();
+            self.record_fill(&struct_expr.path, fields);
+        }
+
+        if !added.is_empty() {
+            let (mut first, second) = match self.options.fields_first {
+                true => (added, mem::take(&mut struct_expr.fields)),
+                false => (mem::take(&mut struct_expr.fields), added),
+            };
+
+            if !second.is_empty() {
+                if !first.empty_or_trailing() {
+                    first.push_punct(Token![,](span));
+                }
+
+                first.extend(second.into_pairs());
+            }
+
+            // The fields must always come before an existing `..rest`, and
+            // be separated from it with a comma
+            if struct_expr.dot2_token.is_some() && !first.empty_or_trailing() {
+                first.push_punct(Token![,](span));
+            }
+
+            struct_expr.fields = first;
+        }
+
+        if variant {
+            return;
+        }
+
+        // Types with a template get its fields (that they don't already set)
+        // instead of a `..rest`
+        let struct_ident = &path.segments.last().unwrap().ident;
+        let template = self
+            .options
+            .templates
+            .iter()
+            .find(|template| template.path.segments.last().unwrap().ident == *struct_ident);
+
+        if let Some(template) = template {
+            if struct_expr.rest.is_none() {
+                let missing: Vec<FieldValue> = template
+                    .fields
+                    .iter()
+                    .filter(|field| {
+                        !struct_expr
+                            .fields
+                            .iter()
+                            .any(|existing| existing.member == field.member)
+                    })
+                    .cloned()
+                    .collect();
+
+                self.check_required_fields(struct_expr);
+
+                let mut inserted = Vec::new();
+
+                for mut field in missing {
+                    inserted.push(field.to_token_stream().to_string());
+
+                    // Struct expressions in the template are filled like the
+                    // ones nested in this struct expression
+                    let outer_depth = mem::replace(&mut self.depth, depth);
+                    self.visit_field_value_mut(&mut field);
+                    self.depth = outer_depth;
+
+                    if !struct_expr.fields.empty_or_trailing() {
+                        struct_expr.fields.push_punct(Token![,](span));
+                    }
+
+                    struct_expr.fields.push_value(field);
+                }
+
+                if !inserted.is_empty() {
+                    let fields = inserted.join(", ");
+                    self.record_fill(&struct_expr.path, fields);
+                }
+            }
+
+            return;
+        }
+
+        // Add `..Default::default()` to structs that don't have a ..rest
+        // initializer
+        if struct_expr.dot2_token.is_none() && struct_expr.rest.is_none() {
+            self.check_required_fields(struct_expr);
+
+            let rest = self.fill_expr(path, &struct_expr.path);
+            let rest = respan(rest.into_token_stream(), span);
+            let rest: Expr = parse_quote! { #rest };
+
+            // Make sure fields have trailing comma
+            if !struct_expr.fields.empty_or_trailing() {
+                struct_expr.fields.push_punct(Token![,](span));
+            }
+
+            // Add the ..Default::default()
+            struct_expr.dot2_token = Some(Token![..](span));
+            let rest_text = rest
+                .to_token_stream()
+                .to_string()
+                .replace(" :: ", "::")
+                .replace(":: ", "::")
+                .replace(" (", "(");
+            struct_expr.rest = Some(Box::new(rest));
+
+            self.record_fill(&struct_expr.path, format!("..{}", rest_text));
+        }
+    }
+
+    fn visit_local_mut(&mut self, local: &mut Local) {
+        let outer_rules = self
+            .take_rules_override(&mut local.attrs)
+            .map(|rules| mem::replace(&mut self.options.rules, rules));
+
+        if self.options.use_binding_type {
+            if let (Pat::Type(pat), Some((_, init))) = (&local.pat, &local.init) {
+                let mut ty = &*pat.ty;
+
+                while let Type::Group(group) = ty {
+                    ty = &group.elem;
+                }
+
+                if let (Type::Path(ty), Expr::Struct(_)) = (ty, ungroup(init)) {
+                    if ty.qself.is_none() {
+                        self.binding_type = Some(ty.path.clone());
+                    }
+                }
+            }
+        }
+
+        if let Some((_, init)) = &local.init {
+            self.in_binding = matches!(ungroup(init), Expr::Struct(_));
+        }
+
+        visit_local_mut(self, local);
+
+        // In case the initializer wasn't a struct expression after all
+        self.binding_type = None;
+        self.in_binding = false;
+
+        if let Some(outer_rules) = outer_rules {
+            self.options.rules = outer_rules;
+        }
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        // Only the tail expression of a block inherits its return position
+        let (in_return, in_returned) = (self.in_return, self.in_returned);
+        let tail = block.stmts.len().wrapping_sub(1);
+
+        for (index, stmt) in block.stmts.iter_mut().enumerate() {
+            let is_tail = index == tail && matches!(stmt, Stmt::Expr(_));
+            self.in_return = in_return && is_tail;
+            self.in_returned = in_returned && is_tail;
+            self.visit_stmt_mut(stmt);
+        }
+
+        self.in_return = in_return;
+        self.in_returned = in_returned;
+    }
+
+    fn visit_expr_block_mut(&mut self, expr: &mut ExprBlock) {
+        let outer_rules = self
+            .take_rules_override(&mut expr.attrs)
+            .map(|rules| mem::replace(&mut self.options.rules, rules));
+
+        visit_expr_block_mut(self, expr);
+
+        if let Some(outer_rules) = outer_rules {
+            self.options.rules = outer_rules;
+        }
+    }
+
+    fn visit_expr_repeat_mut(&mut self, expr: &mut ExprRepeat) {
+        for attr in &mut expr.attrs {
+            self.visit_attribute_mut(attr);
+        }
+
+        self.visit_expr_mut(&mut expr.expr);
+
+        // The length of an array is a const context
+        let const_fn = self.const_fn.replace(expr.len.span());
+        self.visit_expr_mut(&mut expr.len);
+        self.const_fn = const_fn;
+    }
+
+    fn visit_generic_argument_mut(&mut self, arg: &mut GenericArgument) {
+        match arg {
+            GenericArgument::Const(expr) => self.visit_const_argument(expr),
+            arg => visit_generic_argument_mut(self, arg),
+        }
+    }
+
+    fn visit_generic_method_argument_mut(&mut self, arg: &mut GenericMethodArgument) {
+        match arg {
+            GenericMethodArgument::Const(expr) => self.visit_const_argument(expr),
+            arg => visit_generic_method_argument_mut(self, arg),
+        }
+    }
+
+    fn visit_expr_if_mut(&mut self, expr: &mut ExprIf) {
+        for attr in &mut expr.attrs {
+            self.visit_attribute_mut(attr);
+        }
+
+        // The condition is never returned, but the branches are
+        let outer = self.leave_return();
+        self.visit_expr_mut(&mut expr.cond);
+        (self.in_return, self.in_returned) = outer;
+
+        self.visit_block_mut(&mut expr.then_branch);
+
+        if let Some((_, else_branch)) = &mut expr.else_branch {
+            self.visit_expr_mut(else_branch);
+        }
+    }
+
+    fn visit_expr_match_mut(&mut self, expr: &mut ExprMatch) {
+        for attr in &mut expr.attrs {
+            self.visit_attribute_mut(attr);
+        }
+
+        let outer = self.leave_return();
+        self.visit_expr_mut(&mut expr.expr);
+        (self.in_return, self.in_returned) = outer;
+
+        for arm in &mut expr.arms {
+            self.visit_arm_mut(arm);
+        }
+    }
+
+    fn visit_arm_mut(&mut self, arm: &mut Arm) {
+        for attr in &mut arm.attrs {
+            self.visit_attribute_mut(attr);
+        }
+
+        let outer = self.leave_return();
+        self.visit_pat_mut(&mut arm.pat);
+
+        if let Some((_, guard)) = &mut arm.guard {
+            self.visit_expr_mut(guard);
+        }
+
+        (self.in_return, self.in_returned) = outer;
+        self.visit_expr_mut(&mut arm.body);
+    }
+
+    fn visit_expr_return_mut(&mut self, expr: &mut ExprReturn) {
+        let outer = (
+            mem::replace(&mut self.in_return, true),
+            mem::replace(&mut self.in_returned, true),
+        );
+        visit_expr_return_mut(self, expr);
+        (self.in_return, self.in_returned) = outer;
+    }
+
+    fn visit_expr_closure_mut(&mut self, expr: &mut ExprClosure) {
+        // The body of a closure is never returned from the function itself,
+        // unless the function returns an `impl Trait` built from it (like an
+        // iterator), even if it's passed to a function to build it
+        let outer = self.enter_closure();
+        visit_expr_closure_mut(self, expr);
+        (self.in_return, self.in_returned) = outer;
+    }
+
+    fn visit_expr_async_mut(&mut self, expr: &mut ExprAsync) {
+        let outer = self.enter_closure();
+        visit_expr_async_mut(self, expr);
+        (self.in_return, self.in_returned) = outer;
+    }
+
+    fn visit_macro_mut(&mut self, mac: &mut Macro) {
+        let name = &mac.path.segments.last().unwrap().ident;
+
+        // `verbatim!` is never looked inside, even if it's listed in `macros`
+        if name == "verbatim" || !self.options.macros.contains(name) {
+            return;
+        }
+
+        if let Some(tokens) = self.process_macro_args(mac.tokens.clone()) {
+            mac.tokens = tokens;
+        }
+    }
+
+    fn visit_stmt_mut(&mut self, stmt: &mut Stmt) {
+        // syn doesn't parse `let ... else { ... };` statements, and leaves them
+        // as verbatim tokens instead
+        if let Stmt::Semi(Expr::Verbatim(tokens), _) = stmt {
+            if let Ok(mut let_else) = parse2::<LetElse>(tokens.clone()) {
+                self.visit_local_mut(&mut let_else.local);
+                self.visit_block_mut(&mut let_else.diverge);
+                *tokens = let_else.into_token_stream();
+            }
+
+            return;
+        }
+
+        visit_stmt_mut(self, stmt);
+    }
+
+    fn visit_item_mut(&mut self, item: &mut Item) {
+        // Macro invocations in statement position are parsed as items, but
+        // they aren't nested item definitions
+        if let Item::Macro(item_macro) = item {
+            if item_macro.ident.is_none() {
+                self.visit_macro_mut(&mut item_macro.mac);
+            }
+        }
+    }
+}
+
+/// Removes the `#[autodefault(...)]` attributes that `autodefault` itself
+/// would have removed from an item (like skip markers), without filling
+/// anything, for unprocessed copies of the item (like the one emitted by
+/// `test_only`)
+struct StripMarkers {
+    /// True while inside a function, whose nested items are left alone
+    in_fn: bool,
+}
+
+impl StripMarkers {
+    fn strip(attrs: &mut Vec<Attribute>) {
+        attrs.retain(|attr| !attr.path.is_ident("autodefault"));
+    }
+}
+
+impl VisitMut for StripMarkers {
+    fn visit_expr_struct_mut(&mut self, expr: &mut ExprStruct) {
+        StripMarkers::strip(&mut expr.attrs);
+        visit_expr_struct_mut(self, expr);
+    }
+
+    fn visit_expr_block_mut(&mut self, expr: &mut ExprBlock) {
+        StripMarkers::strip(&mut expr.attrs);
+        visit_expr_block_mut(self, expr);
+    }
+
+    fn visit_local_mut(&mut self, local: &mut Local) {
+        StripMarkers::strip(&mut local.attrs);
+        visit_local_mut(self, local);
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        let in_fn = mem::replace(&mut self.in_fn, true);
+        visit_block_mut(self, block);
+        self.in_fn = in_fn;
+    }
+
+    fn visit_item_mut(&mut self, item: &mut Item) {
+        if !self.in_fn {
+            visit_item_mut(self, item);
+        }
+    }
+}
+
+/// A `let ... else { ... }` statement, without its trailing semicolon
+struct LetElse {
+    local: Local,
+    else_token: Token![else],
+    diverge: Block,
+}
+
+impl Parse for LetElse {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let let_token = input.parse()?;
+        let mut pat: Pat = input.parse()?;
+
+        if input.peek(Token![:]) {
+            pat = Pat::Type(syn::PatType {
+                attrs: Vec::new(),
+                pat: Box::new(pat),
+                colon_token: input.parse()?,
+                ty: input.parse()?,
+            });
+        }
+
+        let eq_token = input.parse()?;
+        let init: Expr = input.parse()?;
+
+        Ok(LetElse {
+            local: Local {
+                attrs,
+                let_token,
+                pat,
+                init: Some((eq_token, Box::new(init))),
+                semi_token: Default::default(),
+            },
+            else_token: input.parse()?,
+            diverge: input.parse()?,
+        })
+    }
+}
+
+impl ToTokens for LetElse {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        let Local {
+            attrs,
+            let_token,
+            pat,
+            init,
+            ..
+        } = &self.local;
+
+        let (eq_token, init) = init.as_ref().unwrap();
+        let else_token = &self.else_token;
+        let diverge = &self.diverge;
+
+        tokens.extend(quote! {
+            #(#attrs)* #let_token #pat #eq_token #init #else_token #diverge
+        });
+    }
+}
+
+/// Format the location of a span as `file:line:column`, for reporting
+#[cfg(feature = "span-locations")]
+fn span_location(span: Span) -> String {
+    let start = span.start();
+    format!("{}:{}:{}", span.file(), start.line, start.column + 1)
+}
+
+#[cfg(not(feature = "span-locations"))]
+fn span_location(_span: Span) -> String {
+    "unknown location (enable the span-locations feature)".to_owned()
+}
+
+/// Summarize the filled struct expressions, with the number of each type
+/// (most common first)
+fn summarize(filled: &[(String, Span, String)]) -> String {
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+
+    for (name, _, _) in filled {
+        match counts.iter_mut().find(|(counted, _)| counted == name) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((name, 1)),
+        }
+    }
+
+    counts.sort_by(|(name1, count1), (name2, count2)| count2.cmp(count1).then(name1.cmp(name2)));
+
+    let counts: Vec<String> = counts
+        .iter()
+        .map(|(name, count)| format!("{}({})", name, count))
+        .collect();
+
+    format!(
+        "autodefault: filled {} literals across {} types: {}",
+        filled.len(),
+        counts.len(),
+        counts.join(", ")
+    )
+}
+
+/// Move every token in a token stream (including those nested in groups) to
+/// the location of a span. Each token keeps its own name resolution, so that
+/// user-written expressions (like the one in `fill(Type = expr)`) still
+/// resolve where they were written, even inside of a `macro_rules!` macro.
+fn respan(tokens: TokenStream2, span: Span) -> TokenStream2 {
+    tokens
+        .into_iter()
+        .map(|mut token| {
+            let located = token.span().located_at(span);
+
+            if let TokenTree::Group(group) = &token {
+                let mut respanned = Group::new(group.delimiter(), respan(group.stream(), span));
+                respanned.set_span(located);
+                token = TokenTree::Group(respanned);
+            } else {
+                token.set_span(located);
+            }
+
+            token
+        })
+        .collect()
+}
+
+/// Attach a warning to a span. `proc_macro::Diagnostic` is unstable, so this
+/// only does anything with the `nightly` feature.
+#[cfg(feature = "nightly")]
+fn emit_warning(span: Span, message: &str) {
+    if proc_macro::is_available() {
+        span.unwrap().warning(message).emit();
+    }
+}
+
+#[cfg(not(feature = "nightly"))]
+fn emit_warning(_span: Span, _message: &str) {}
+
+/// The default for `recursion_limit`
+const DEFAULT_RECURSION_LIMIT: usize = 128;
+
+/// Find the first delimited group nested more than `limit` levels deep in a
+/// token stream. This is done without recursion, since the point is to avoid
+/// overflowing the stack while processing very deeply nested items.
+fn find_too_deep(tokens: TokenStream2, limit: usize) -> Option<Span> {
+    let mut stack = vec![tokens.into_iter()];
+
+    while let Some(tokens) = stack.last_mut() {
+        match tokens.next() {
+            Some(TokenTree::Group(group)) => {
+                if stack.len() > limit {
+                    return Some(group.span());
+                }
+
+                stack.push(group.stream().into_iter());
+            }
+            Some(_) => {}
+            None => {
+                stack.pop();
+            }
+        }
+    }
+
+    None
+}
+
+/// Remove `autodefault`'s own markers (like `#[autodefault(skip)]`) from an
+/// item that's emitted without being processed, since attributes on
+/// expressions and statements aren't allowed on stable. If the item doesn't
+/// parse, it's returned as-is.
+fn without_markers(item: TokenStream2) -> TokenStream2 {
+    let mut stmt = match parse2(item.clone()) {
+        Ok(item) => Stmt::Item(item),
+        Err(_) => match parse2(item.clone()) {
+            Ok(stmt @ Stmt::Local(_)) => stmt,
+            _ => return item,
+        },
+    };
+
+    StripMarkers { in_fn: false }.visit_stmt_mut(&mut stmt);
+    stmt.into_token_stream()
+}
+
+/// Emit an error alongside the unmodified item. Keeping the item means that
+/// the error doesn't cascade into more errors wherever the item is used, and
+/// that IDEs like rust-analyzer can still understand it (for instance, to
+/// complete field names in a half-written struct expression).
+fn error_with_item(err: syn::Error, item: TokenStream2) -> TokenStream2 {
+    let mut tokens = item;
+    tokens.extend(err.into_compile_error());
+    tokens
+}
+
+/// Remove the `use(...)` clause from the arguments to `#[autodefault(...)]`
+fn without_use(attr: TokenStream2) -> TokenStream2 {
+    let mut tokens = attr.into_iter().peekable();
+    let mut rest = TokenStream2::new();
+
+    while let Some(token) = tokens.next() {
+        match &token {
+            TokenTree::Ident(ident) if ident == "use" => {
+                tokens.next();
+
+                if matches!(tokens.peek(), Some(TokenTree::Punct(punct)) if punct.as_char() == ',')
+                {
+                    tokens.next();
+                }
+            }
+            _ => rest.extend(Some(token)),
+        }
+    }
+
+    rest
+}
+
+fn autodefault_impl(attr: TokenStream2, item: TokenStream2) -> TokenStream2 {
+    let original = item.clone();
+
+    let options: Options = match parse2(attr.clone()) {
+        Ok(options) => options,
+        Err(err) => return error_with_item(err, without_markers(original)),
+    };
+
+    // Hand the item off to the macro generated by `define_filters!`, which
+    // applies `#[autodefault]` again with its options added
+    if let Some(name) = options.use_filters {
+        let args = without_use(attr);
+        return quote! { #name! { (#args) #original } };
+    }
+
+    let recursion_limit = options.recursion_limit.unwrap_or(DEFAULT_RECURSION_LIMIT);
+
+    if let Some(span) = find_too_deep(item.clone(), recursion_limit) {
+        let err = syn::Error::new(
+            span,
+            format!(
+                "This item is nested more than {} levels deep, which could overflow \
+                the stack; use recursion_limit = N to raise the limit",
+                recursion_limit
+            ),
+        );
+
+        return error_with_item(err, original);
+    }
+
+    let mut item = match parse2(item.clone()) {
+        Ok(item) => Stmt::Item(item),
+        // On nightly, #[autodefault] can also be applied to a `let` statement
+        Err(err) => match parse2(item) {
+            Ok(stmt @ Stmt::Local(_)) => stmt,
+            _ => return error_with_item(err, original),
+        },
+    };
+
+    let mut visitor = AutodefaultVisitor::new(options);
+
+    // With `test_only`, the item is also emitted without being processed, for
+    // non-test builds
+    let plain = match visitor.options.test_only {
+        true => Some(item.clone()),
+        false => None,
+    };
+
+    let result = match &mut item {
+        Stmt::Item(item) => visitor.process_item(item),
+        Stmt::Local(local) => {
+            visitor.process_local(local);
+            Ok(())
+        }
+        // `item` was parsed as either an item or a `let` statement
+        _ => unreachable!(),
+    };
+
+    match result {
+        Ok(()) => {
+            if let Some(max_literals) = visitor.options.max_literals {
+                if visitor.filled.len() > max_literals {
+                    let err = syn::Error::new(
+                        visitor.filled[max_literals].1,
+                        format!(
+                            "This item has {} struct expressions to fill, more than the \
+                            limit of {}; use max_literals = N to raise the limit",
+                            visitor.filled.len(),
+                            max_literals
+                        ),
+                    );
+
+                    return error_with_item(err, without_markers(original));
+                }
+            }
+
+            if visitor.options.require_effect && visitor.filled.is_empty() {
+                let span = match &item {
+                    Stmt::Item(Item::Fn(item_fn)) => item_fn.sig.ident.span(),
+                    _ => Span::call_site(),
+                };
+
+                visitor.errors.push(syn::Error::new(
+                    span,
+                    "#[autodefault] didn't fill any struct expressions here; remove it",
+                ));
+            }
+
+            if visitor.options.check_filters {
+                for filter in visitor.options.rules.filters() {
+                    let unmatched = filter.unmatched(&visitor.seen_paths);
+
+                    visitor
+                        .errors
+                        .extend(unmatched.into_iter().map(|(entry, span)| {
+                            syn::Error::new(
+                                span,
+                                format!("'{}' didn't match any struct expression", entry),
+                            )
+                        }));
+                }
+            }
+
+            let mut tokens = match plain {
+                Some(mut plain) => {
+                    StripMarkers { in_fn: false }.visit_stmt_mut(&mut plain);
+
+                    quote! {
+                        #[cfg(test)]
+                        #item
+                        #[cfg(not(test))]
+                        #plain
+                    }
+                }
+                None => item.into_token_stream(),
+            };
+            tokens.extend(visitor.errors.iter().map(syn::Error::to_compile_error));
+
+            let count_const = visitor.options.count_const.as_ref().map(|name| {
+                let count = Literal::usize_unsuffixed(visitor.filled.len());
+
+                quote! {
+                    #[allow(dead_code)]
+                    const #name: usize = #count;
+                }
+            });
+
+            tokens.extend(count_const.clone());
+
+            if visitor.options.dry_run {
+                eprintln!(
+                    "autodefault: dry run found {} struct expressions to fill:",
+                    visitor.filled.len()
+                );
+
+                for (name, span, _) in &visitor.filled {
+                    eprintln!("    {} at {}", name, span_location(*span));
+                }
+
+                let mut original = without_markers(original);
+                original.extend(count_const);
+                original.extend(visitor.errors.iter().map(syn::Error::to_compile_error));
+                return original;
+            }
+
+            if visitor.options.warn {
+                for (name, span, rest) in &visitor.filled {
+                    emit_warning(
+                        *span,
+                        &format!("autodefault: inserted {} for `{}`", rest, name),
+                    );
+                }
+            }
+
+            if visitor.options.summary {
+                eprintln!("{}", summarize(&visitor.filled));
+            }
+
+            if visitor.options.debug {
+                eprintln!("autodefault: expanded to:\n{}", tokens);
+            }
+
+            tokens
+        }
+        Err(err) => error_with_item(err, without_markers(original)),
+    }
+}
+
+/// Check if an attribute is an `#[autodefault]` attribute (possibly written
+/// as `#[autodefault::autodefault]`)
+fn is_autodefault_attr(attr: &Attribute) -> bool {
+    attr.path.segments.last().unwrap().ident == "autodefault"
+}
+
+/// Prepend `config(...)` to the arguments of every `#[autodefault]` attribute
+/// on these items, including methods and items in nested modules
+fn apply_config(items: &mut [Item], config: &TokenStream2) {
+    let add_config = |attrs: &mut Vec<Attribute>| {
+        for attr in attrs.iter_mut().filter(|attr| is_autodefault_attr(attr)) {
+            let args = match parse2::<Group>(attr.tokens.clone()) {
+                Ok(group) => group.stream(),
+                Err(_) => TokenStream2::new(),
+            };
+
+            attr.tokens = if args.is_empty() {
+                quote! { (config(#config)) }
+            } else {
+                quote! { (config(#config), #args) }
+            };
+        }
+    };
+
+    for item in items {
+        match item {
+            Item::Fn(item_fn) => add_config(&mut item_fn.attrs),
+            Item::Impl(item_impl) => {
+                add_config(&mut item_impl.attrs);
+
+                for impl_item in &mut item_impl.items {
+                    if let ImplItem::Method(method) = impl_item {
+                        add_config(&mut method.attrs);
+                    }
+                }
+            }
+            Item::Trait(item_trait) => {
+                add_config(&mut item_trait.attrs);
+
+                for trait_item in &mut item_trait.items {
+                    if let TraitItem::Method(method) = trait_item {
+                        add_config(&mut method.attrs);
+                    }
+                }
+            }
+            Item::Mod(item_mod) => {
+                add_config(&mut item_mod.attrs);
+
+                if let Some((_, items)) = &mut item_mod.content {
+                    apply_config(items, config);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn config_impl(attr: TokenStream2, item: TokenStream2) -> TokenStream2 {
+    let original = item.clone();
+
+    // Check the options now, so that errors point at the config
+    match parse2::<Options>(attr.clone()) {
+        Ok(Options {
+            use_filters: Some(name),
+            ..
+        }) => {
+            let err = syn::Error::new(name.span(), "'use' can't be given in #[autodefault_config]");
+            return error_with_item(err, original);
+        }
+        Ok(_) => {}
+        Err(err) => return error_with_item(err, original),
+    }
+
+    let mut item_mod: ItemMod = match parse2(item) {
+        Ok(item_mod) => item_mod,
+        Err(err) => return error_with_item(err, original),
+    };
+
+    match &mut item_mod.content {
+        Some((_, items)) => apply_config(items, &attr),
+        None => {
+            let err = syn::Error::new_spanned(
+                &item_mod,
+                "#[autodefault_config] can only be applied to inline modules",
+            );
+
+            return error_with_item(err, original);
+        }
+    }
+
+    item_mod.into_token_stream()
+}
+
+/// A single `name = options...` entry in `define_filters!`
+struct FilterDefinition {
+    name: Ident,
+    options: TokenStream2,
+}
+
+impl Parse for FilterDefinition {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        input.parse::<Token![=]>()?;
+
+        let mut options = TokenStream2::new();
+
+        while !input.is_empty() && !input.peek(Token![;]) {
+            options.extend(Some(input.parse::<TokenTree>()?));
+        }
+
+        Ok(FilterDefinition { name, options })
+    }
+}
+
+fn define_filters_impl(input: TokenStream2) -> TokenStream2 {
+    let definitions =
+        match Punctuated::<FilterDefinition, Token![;]>::parse_terminated.parse2(input) {
+            Ok(definitions) => definitions,
+            Err(err) => return err.into_compile_error(),
+        };
+
+    let mut tokens = TokenStream2::new();
+
+    for FilterDefinition { name, options } in definitions {
+        match parse2::<Options>(options.clone()) {
+            Ok(Options {
+                use_filters: Some(name),
+                ..
+            }) => {
+                tokens.extend(
+                    syn::Error::new(name.span(), "'use' can't be given in define_filters!")
+                        .into_compile_error(),
+                );
+                continue;
+            }
+            Ok(_) => {}
+            Err(err) => {
+                tokens.extend(err.into_compile_error());
+                continue;
+            }
+        }
+
+        tokens.extend(quote! {
+            #[allow(unused_macros)]
+            macro_rules! #name {
+                (($($args:tt)*) $($item:tt)*) => {
+                    #[::autodefault::autodefault(config(#options), $($args)*)]
+                    $($item)*
+                };
+            }
+        });
+    }
+
+    tokens
+}
+
+fn skip_impl(attr: TokenStream2, item: TokenStream2) -> TokenStream2 {
+    if !attr.is_empty() {
+        return syn::Error::new_spanned(attr, "#[autodefault::skip] doesn't take any arguments")
+            .into_compile_error();
+    }
+
+    let item: Item = match parse2(item) {
+        Ok(item) => item,
+        Err(err) => return err.into_compile_error(),
+    };
+
+    // `#[autodefault]` finds this attribute on the struct itself, before it's
+    // expanded, so there's nothing else to do here
+    match item {
+        Item::Struct(item) => item.into_token_stream(),
+        item => syn::Error::new_spanned(
+            item,
+            "#[autodefault::skip] can only be applied to struct definitions",
+        )
+        .into_compile_error(),
+    }
+}
+
+/// The input to `autodefault_match!`: a scrutinee, followed by the arms of
+/// the `match`
+struct MatchInput {
+    expr: Expr,
+    arms: Vec<Arm>,
+}
+
+impl Parse for MatchInput {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let expr = input.call(Expr::parse_without_eager_brace)?;
+        input.parse::<Token![,]>()?;
+
+        let mut arms = Vec::new();
+
+        while !input.is_empty() {
+            arms.push(input.parse()?);
+        }
+
+        Ok(MatchInput { expr, arms })
+    }
+}
+
+fn autodefault_match_impl(input: TokenStream2) -> TokenStream2 {
+    let MatchInput { expr, arms } = match parse2(input) {
+        Ok(input) => input,
+        Err(err) => return err.into_compile_error(),
+    };
+
+    let mut expr: Expr = parse_quote! {
+        match #expr {
+            #(#arms)*
+        }
+    };
+
+    let mut visitor = AutodefaultVisitor::new(Options::default());
+    visitor.visit_expr_mut(&mut expr);
+
+    let mut tokens = expr.into_token_stream();
+    tokens.extend(visitor.errors.iter().map(syn::Error::to_compile_error));
+    tokens
+}
+
+/// Modify a function (or all the functions in a module or impl block) such
+/// that some or all struct expressions include `..Default::default()`.
+///
+/// See [module][crate] docs for details.
+#[proc_macro_attribute]
+pub fn autodefault(attr: TokenStream, item: TokenStream) -> TokenStream {
+    autodefault_impl(attr.into(), item.into()).into()
+}
+
+/// Set the default options for every `#[autodefault]` in a module.
+///
+/// See [module][crate] docs for details.
+#[proc_macro_attribute]
+pub fn autodefault_config(attr: TokenStream, item: TokenStream) -> TokenStream {
+    config_impl(attr.into(), item.into()).into()
+}
+
+/// Define a named set of options, which can be used with
+/// `#[autodefault(use(name))]`.
+///
+/// See [module][crate] docs for details.
+#[proc_macro]
+pub fn define_filters(input: TokenStream) -> TokenStream {
+    define_filters_impl(input.into()).into()
+}
+
+/// Build a `match` expression in which every struct expression includes
+/// `..Default::default()`, written as `autodefault_match!(scrutinee, arms...)`.
+///
+/// See [module][crate] docs for details.
+#[proc_macro]
+pub fn autodefault_match(input: TokenStream) -> TokenStream {
+    autodefault_match_impl(input.into()).into()
+}
+
+/// Expand to the given tokens unchanged. `#[autodefault]` never looks inside
+/// `verbatim!`, so it can be used to protect a region from processing.
+///
+/// See [module][crate] docs for details.
+#[proc_macro]
+pub fn verbatim(input: TokenStream) -> TokenStream {
+    input
+}
+
+/// Mark a struct such that `#[autodefault]` never adds `..Default::default()`
+/// to its literals.
+///
+/// See [module][crate] docs for details and limitations.
+#[proc_macro_attribute]
+pub fn skip(attr: TokenStream, item: TokenStream) -> TokenStream {
+    skip_impl(attr.into(), item.into()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proc_macro2::{Delimiter, TokenStream as TokenStream2};
+    use quote::quote;
+
+    #[test]
+    fn it_works() {
+        let input = quote! {
+            fn demo () {
+                let x = Foo {a: 10, b: 10};
+            }
+        };
+        let output: TokenStream2 = autodefault_impl(TokenStream2::new(), input);
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo () {
+                    let x = Foo {a: 10, b: 10, ..::core::default::Default::default()};
+                }
+            }
+            .to_string(),
+        )
+    }
+
+    #[test]
+    fn trailing_comma() {
+        let input = quote! {
+            fn demo () {
+                let x = Foo {a: 10, b: 10, };
+            }
+        };
+        let output: TokenStream2 = autodefault_impl(TokenStream2::new(), input);
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo () {
+                    let x = Foo {a: 10, b: 10, ..::core::default::Default::default()};
+                }
+            }
+            .to_string(),
+        )
+    }
+
+    #[test]
+    fn exact_tokens() {
+        let input = quote! {
+            fn demo() {
+                let x = Foo { a: 10 };
+                let y = Foo { a: 10, };
+            }
+        };
+        let output: TokenStream2 = autodefault_impl(TokenStream2::new(), input);
+
+        assert_eq!(
+            output.to_string(),
+            "fn demo () { \
+                let x = Foo { a : 10 , .. :: core :: default :: Default :: default () } ; \
+                let y = Foo { a : 10 , .. :: core :: default :: Default :: default () } ; \
+            }",
+        )
+    }
+
+    #[test]
+    #[cfg(feature = "span-locations")]
+    fn inserted_spans() {
+        let input: TokenStream2 = "fn demo() {\n    let x = Foo { a: 10 };\n}"
+            .parse()
+            .unwrap();
+        let output: ItemFn = parse2(autodefault_impl(TokenStream2::new(), input)).unwrap();
+
+        let struct_expr = match &output.block.stmts[0] {
+            Stmt::Local(Local {
+                init: Some((_, init)),
+                ..
+            }) => match &**init {
+                Expr::Struct(struct_expr) => struct_expr,
+                _ => panic!("expected a struct expression"),
+            },
+            _ => panic!("expected a let binding"),
+        };
+
+        let start = struct_expr.path.span().start();
+        let inserted = struct_expr
+            .dot2_token
+            .to_token_stream()
+            .into_iter()
+            .chain(struct_expr.rest.to_token_stream());
+
+        for token in inserted {
+            assert_eq!(token.span().start(), start);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "span-locations")]
+    fn validation_spans() {
+        let input: TokenStream2 = "fn demo() {\n    \
+            let a = Foo { a: 1, b: 2 };\n    \
+            let b = Bar { a: 1 };\n    \
+            let c = Foo { a: 1, b: 2 };\n\
+            }"
+        .parse()
+        .unwrap();
+
+        let output = autodefault_impl(quote! { require_field(b), checked }, input);
+
+        let lines = |name: &str| -> Vec<usize> {
+            fn find(tokens: TokenStream2, name: &str, lines: &mut Vec<usize>) {
+                for token in tokens {
+                    match token {
+                        TokenTree::Group(group) => find(group.stream(), name, lines),
+                        TokenTree::Ident(ident) if ident == name => {
+                            lines.push(ident.span().start().line)
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
+            let mut lines = Vec::new();
+            find(output.clone(), name, &mut lines);
+            lines
+        };
+
+        // The error is reported at the struct expression missing `b`, and the
+        // assertions for `checked` at the first struct expression of each type
+        assert_eq!(lines("compile_error"), [3]);
+        assert_eq!(lines("assert_default"), [2, 2, 3, 3]);
+    }
+
+    #[test]
+    fn shorthand_fields() {
+        let input = quote! {
+            fn demo(x: i32, y: i32) {
+                let a = Foo { x };
+                let b = Foo { x, };
+                let c = Foo { x, y };
+                let d = Foo { x, y, };
+                let e = Foo { a: 10, x };
+            }
+        };
+        let output: TokenStream2 = autodefault_impl(TokenStream2::new(), input);
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo(x: i32, y: i32) {
+                    let a = Foo { x, ..::core::default::Default::default() };
+                    let b = Foo { x, ..::core::default::Default::default() };
+                    let c = Foo { x, y, ..::core::default::Default::default() };
+                    let d = Foo { x, y, ..::core::default::Default::default() };
+                    let e = Foo { a: 10, x, ..::core::default::Default::default() };
+                }
+            }
+            .to_string(),
+        );
+
+        let output = autodefault_impl(
+            quote! { field(z = 1) },
+            quote! {
+                fn demo(x: i32) {
+                    let a = Foo { x };
+                    let b = Foo { x, };
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo(x: i32) {
+                    let a = Foo { x, z: 1, ..::core::default::Default::default() };
+                    let b = Foo { x, z: 1, ..::core::default::Default::default() };
+                }
+            }
+            .to_string(),
+        )
+    }
+
+    #[test]
+    fn empty_struct() {
+        let input = quote! {
+            fn demo () {
+                let x = Foo {};
+            }
+        };
+        let output: TokenStream2 = autodefault_impl(TokenStream2::new(), input);
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo () {
+                    let x = Foo {..::core::default::Default::default()};
+                }
+            }
+            .to_string(),
+        )
+    }
+
+    #[test]
+    fn existing_spread() {
+        let input = quote! {
+            fn demo () {
+                let x = Foo {a: 10, b: 10, ..foo()};
+            }
+        };
+        let output: TokenStream2 = autodefault_impl(TokenStream2::new(), input);
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo () {
+                    let x = Foo {a: 10, b: 10, ..foo()};
+                }
+            }
+            .to_string(),
+        )
+    }
+
+    #[test]
+    fn nested_rest() {
+        let input = quote! {
+            fn demo() {
+                let x = Foo { a: 1, ..Foo { b: 2 } };
+            }
+        };
+        let output: TokenStream2 = autodefault_impl(TokenStream2::new(), input);
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let x = Foo { a: 1, ..Foo { b: 2, ..::core::default::Default::default() } };
+                }
+            }
+            .to_string(),
+        )
+    }
+
+    #[test]
+    fn closures_and_async() {
+        let input = quote! {
+            async fn demo() {
+                let f = |x| Foo { a: x };
+                let g = move |x| { Foo { a: x } };
+                let h = async move { Foo { a: 1 } };
+                let i = async { call(Foo {}).await };
+            }
+        };
+        let output: TokenStream2 = autodefault_impl(TokenStream2::new(), input);
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                async fn demo() {
+                    let f = |x| Foo { a: x, ..::core::default::Default::default() };
+                    let g = move |x| { Foo { a: x, ..::core::default::Default::default() } };
+                    let h = async move { Foo { a: 1, ..::core::default::Default::default() } };
+                    let i = async { call(Foo { ..::core::default::Default::default() }).await };
+                }
+            }
+            .to_string(),
+        )
+    }
+
+    #[test]
+    fn subexpressions() {
+        let input = quote! {
+            fn demo() {
+                let a = builder.with(Foo { a: 1 }).build();
+                let b = map[Key { id: 1 }];
+                let c = items.iter().map(|x| Foo { a: *x }).collect();
+                let d = (Foo {}, [Foo {}], call(Foo {}));
+            }
+        };
+        let output: TokenStream2 = autodefault_impl(TokenStream2::new(), input);
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                    fn demo() {
+                        let a = builder.with(Foo { a: 1, ..::core::default::Default::default() }).build();
+                        let b = map[Key { id: 1, ..::core::default::Default::default() }];
+                        let c = items.iter().map(|x| Foo { a: *x, ..::core::default::Default::default() }).collect();
+                        let d = (
+                            Foo { ..::core::default::Default::default() },
+                            [Foo { ..::core::default::Default::default() }],
+                            call(Foo { ..::core::default::Default::default() })
+                        );
+                    }
+                }.to_string(),
+        )
+    }
+
+    #[test]
+    fn cfg_fields() {
+        let input = quote! {
+            fn demo() {
+                let a = Foo { #[cfg(feature = "x")] a: 1, b: 2 };
+                let b = Foo { a: 1, #[cfg(feature = "x")] b: 2, c: 3 };
+                let c = Foo { a: 1, #[cfg(feature = "x")] b: 2 };
+                let d = Foo { a: 1, #[cfg(feature = "x")] b: 2, };
+            }
+        };
+        let output: TokenStream2 = autodefault_impl(TokenStream2::new(), input);
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Foo {
+                        #[cfg(feature = "x")] a: 1,
+                        b: 2,
+                        ..::core::default::Default::default()
+                    };
+                    let b = Foo {
+                        a: 1,
+                        #[cfg(feature = "x")] b: 2,
+                        c: 3,
+                        ..::core::default::Default::default()
+                    };
+                    let c = Foo {
+                        a: 1,
+                        #[cfg(feature = "x")] b: 2,
+                        ..::core::default::Default::default()
+                    };
+                    let d = Foo {
+                        a: 1,
+                        #[cfg(feature = "x")] b: 2,
+                        ..::core::default::Default::default()
+                    };
+                }
+            }
+            .to_string(),
+        )
+    }
+
+    #[test]
+    fn except() {
+        let output = autodefault_impl(
+            quote! { except(Ignore1, Ignore2) },
+            quote! {
+                fn demo() {
+                    let a = Ignore1 {};
+                    let b = Ignore2 {};
+                    let c = Default1 {};
+                    let d = Default2 {};
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Ignore1 {};
+                    let b = Ignore2 {};
+                    let c = Default1 {..::core::default::Default::default()};
+                    let d = Default2 {..::core::default::Default::default()};
+                }
+            }
+            .to_string()
+        )
+    }
+
+    #[test]
+    fn only() {
+        let output = autodefault_impl(
+            quote! { only(Default1, Default2) },
+            quote! {
+                fn demo() {
+                    let a = Ignore1 {};
+                    let b = Ignore2 {};
+                    let c = Default1 {};
+                    let d = Default2 {};
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Ignore1 {};
+                    let b = Ignore2 {};
+                    let c = Default1 {..::core::default::Default::default()};
+                    let d = Default2 {..::core::default::Default::default()};
+                }
+            }
+            .to_string()
+        )
+    }
+
+    #[test]
+    fn only_prefix() {
+        let output = autodefault_impl(
+            quote! { only(bevy::*, other::module::*) },
+            quote! {
+                fn demo() {
+                    let a = bevy::Camera {};
+                    let b = ::bevy::prelude::Transform {};
+                    let c = Local {};
+                    let d = local::bevy::Thing {};
+                    let e = other::module::Thing {};
+                    let f = other::Thing {};
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = bevy::Camera {..::core::default::Default::default()};
+                    let b = ::bevy::prelude::Transform {..::core::default::Default::default()};
+                    let c = Local {};
+                    let d = local::bevy::Thing {};
+                    let e = other::module::Thing {..::core::default::Default::default()};
+                    let f = other::Thing {};
+                }
+            }
+            .to_string()
+        )
+    }
+
+    #[test]
+    fn filter_order() {
+        let entries: Punctuated<FilterEntry, Token![,]> = parse_quote! { Foo, Bar, Baz, Bar };
+        let filter: Filter = entries.into_iter().collect();
+
+        let names: Vec<String> = filter.names.iter().map(Ident::to_string).collect();
+        assert_eq!(names, ["Foo", "Bar", "Baz"]);
+    }
+
+    #[test]
+    fn unknown_option() {
+        let error = |attr: TokenStream2| parse2::<Options>(attr).err().unwrap().to_string();
+
+        assert_eq!(
+            error(quote! { exept(Foo) }),
+            "Unknown option 'exept'; did you mean 'except'?"
+        );
+        assert!(error(quote! { frobnicate })
+            .starts_with("Unknown option 'frobnicate'; expected one of 'except', 'only', "));
+    }
+
+    #[test]
+    fn only_path_wildcard() {
+        let output = autodefault_impl(
+            quote! { only(proto::_::Message, config::_::*) },
+            quote! {
+                fn demo() {
+                    let a = proto::v1::Message {};
+                    let b = ::proto::v2::Message {};
+                    let c = proto::Message {};
+                    let d = Message {};
+                    let e = proto::v1::Other {};
+                    let f = config::window::Window {};
+                    let g = config::Window {};
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = proto::v1::Message { ..::core::default::Default::default() };
+                    let b = ::proto::v2::Message { ..::core::default::Default::default() };
+                    let c = proto::Message {};
+                    let d = Message {};
+                    let e = proto::v1::Other {};
+                    let f = config::window::Window { ..::core::default::Default::default() };
+                    let g = config::Window {};
+                }
+            }
+            .to_string()
+        )
+    }
+
+    #[test]
+    fn only_from() {
+        let output = autodefault_impl(
+            quote! { only(Foo), only_from(["Bar", "config::*"]) },
+            quote! {
+                fn demo() {
+                    let a = Foo {};
+                    let b = Bar {};
+                    let c = config::Window {};
+                    let d = Baz {};
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Foo { ..::core::default::Default::default() };
+                    let b = Bar { ..::core::default::Default::default() };
+                    let c = config::Window { ..::core::default::Default::default() };
+                    let d = Baz {};
+                }
+            }
+            .to_string()
+        );
+
+        let error = |attr| autodefault_impl(attr, quote! { fn demo() {} }).to_string();
+
+        assert!(error(quote! { only(Foo), except_from(["Bar"]) }).contains("Only one of"));
+        assert!(error(quote! { only_from(["not a name"]) }).contains("compile_error"));
+    }
+
+    #[test]
+    fn check_filters() {
+        let output = autodefault_impl(
+            quote! { except(Foo, Fooo, config::*, proto::_::Message, segments = 3), check_filters },
+            quote! {
+                fn demo() {
+                    let a = Foo {};
+                    let b = config::Window {};
+                }
+            },
+        )
+        .to_string();
+
+        assert!(!output.contains("'Foo' didn't match"));
+        assert!(output.contains("'Fooo' didn't match any struct expression"));
+        assert!(!output.contains("'config::*' didn't match"));
+        assert!(output.contains("'proto::_::Message' didn't match"));
+        assert!(output.contains("'segments = 3' didn't match"));
+
+        // `Self` matches the type of the impl block it's used in
+        let output = autodefault_impl(
+            quote! { only(Self), check_filters },
+            quote! {
+                impl Foo {
+                    fn new() -> Self {
+                        Self { a: 1 }
+                    }
+                }
+            },
+        )
+        .to_string();
+
+        assert!(!output.contains("compile_error"), "{}", output);
+
+        let output = autodefault_impl(
+            quote! { only(Self), check_filters },
+            quote! {
+                impl Foo {
+                    fn new() -> Bar {
+                        Bar { a: 1 }
+                    }
+                }
+            },
+        )
+        .to_string();
+
+        assert!(output.contains("'Self' didn't match"), "{}", output);
+
+        // Repeated entries are only reported once
+        let output = autodefault_impl(
+            quote! { except(a::*, a::*, a::B, a::B, segments = 3), except(segments = 3), check_filters },
+            quote! {
+                fn demo() {}
+            },
+        )
+        .to_string();
+
+        assert_eq!(output.matches("'a::*' didn't match").count(), 1);
+        assert_eq!(output.matches("'a::B' didn't match").count(), 1);
+        assert_eq!(output.matches("'segments = 3' didn't match").count(), 1);
+
+        let output = autodefault_impl(
+            quote! { except(Fooo) },
+            quote! {
+                fn demo() {
+                    let a = Foo {};
+                }
+            },
+        );
+
+        assert!(!output.to_string().contains("compile_error"));
+    }
+
+    #[test]
+    fn only_segments() {
+        let output = autodefault_impl(
+            quote! { only(segments = 2) },
+            quote! {
+                fn demo() {
+                    let a = proto::Message {};
+                    let b = Message {};
+                    let c = proto::v1::Message {};
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = proto::Message { ..::core::default::Default::default() };
+                    let b = Message {};
+                    let c = proto::v1::Message {};
+                }
+            }
+            .to_string()
+        );
+
+        let output = autodefault_impl(quote! { only(segment = 2) }, quote! { fn demo() {} });
+        assert!(output.to_string().contains("compile_error"));
+    }
+
+    #[test]
+    fn turbofish() {
+        let output = autodefault_impl(
+            quote! { only(Grid) },
+            quote! {
+                fn demo() {
+                    let a = Grid::<u8> { width: 3 };
+                    let b = grid::Grid::<u8, u16> { width: 3, };
+                    let c = Other::<u8> {};
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                    fn demo() {
+                        let a = Grid::<u8> { width: 3, ..::core::default::Default::default() };
+                        let b = grid::Grid::<u8, u16> { width: 3, ..::core::default::Default::default() };
+                        let c = Other::<u8> {};
+                    }
+                }.to_string()
+        )
+    }
+
+    #[test]
+    fn generics_without_turbofish() {
+        // This isn't valid expression syntax, so it should be reported as an
+        // error rather than silently treated as a comparison or something
+        let output = autodefault_impl(
+            quote! { only(Grid) },
+            quote! {
+                fn demo() {
+                    let a = Grid<u8> { width: 3 };
+                }
+            },
+        );
+
+        assert!(output.to_string().contains("compile_error"));
+    }
+
+    #[test]
+    fn macro_fragments() {
+        // `macro_rules!` wraps interpolated fragments in invisible groups
+        let fragment = |tokens| TokenTree::Group(Group::new(Delimiter::None, tokens));
+
+        let ty = fragment(quote! { Config });
+        let init = fragment(quote! { Foo { a: 1 } });
+        let rest = fragment(quote! { Default::default() });
+        let value = fragment(quote! { 2 });
+
+        let output = autodefault_impl(
+            quote! { use_binding_type, strip_redundant, only(Config) },
+            quote! {
+                fn demo() {
+                    let x: #ty = #init;
+                    let y = Config { a: #value, ..#rest };
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let x: #ty = Foo { a: 1, ..::core::default::Default::default() };
+                    let y = Config { a: #value, ..::core::default::Default::default() };
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn empty() {
+        let input = quote! {
+            fn demo() {
+                let a = Foo {};
+                let b = Foo { a: 1 };
+            }
+        };
+
+        let output = autodefault_impl(quote! { empty = skip }, input.clone());
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Foo {};
+                    let b = Foo { a: 1, ..::core::default::Default::default() };
+                }
+            }
+            .to_string()
+        );
+
+        let output = autodefault_impl(quote! { empty = fill }, input.clone());
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Foo { ..::core::default::Default::default() };
+                    let b = Foo { a: 1, ..::core::default::Default::default() };
+                }
+            }
+            .to_string()
+        );
+
+        let output = autodefault_impl(quote! { empty = error }, input).to_string();
+        assert!(output.contains("empty struct expression; use Foo::default() instead"));
+
+        let output = autodefault_impl(quote! { empty = sometimes }, quote! { fn demo() {} });
+        assert!(output
+            .to_string()
+            .contains("Expected 'fill', 'skip', or 'error'"));
+    }
+
+    #[test]
+    fn test_only() {
+        let output = autodefault_impl(
+            quote! { test_only },
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 1 };
+                    let b = #[autodefault(skip)] Foo { a: 1, b: 2 };
+
+                    #[autodefault(only(Bar))]
+                    let c = Bar { b: 1 };
+
+                    fn inner() {
+                        let d = #[autodefault(skip)] Foo {};
+                    }
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                #[cfg(test)]
+                fn demo() {
+                    let a = Foo { a: 1, ..::core::default::Default::default() };
+                    let b = Foo { a: 1, b: 2 };
+
+                    let c = Bar { b: 1, ..::core::default::Default::default() };
+
+                    fn inner() {
+                        let d = #[autodefault(skip)] Foo {};
+                    }
+                }
+                #[cfg(not(test))]
+                fn demo() {
+                    let a = Foo { a: 1 };
+                    let b = Foo { a: 1, b: 2 };
+
+                    let c = Bar { b: 1 };
+
+                    fn inner() {
+                        let d = #[autodefault(skip)] Foo {};
+                    }
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn strip() {
+        let original = quote! {
+            fn demo() {
+                let a = Foo { a: 10 };
+                let b = Foo { a: 10, };
+                let c = Foo {};
+                let d = Foo { a: 10, ..base };
+                let e = Skipped { a: 10, ..Default::default() };
+            }
+        };
+
+        let filled = autodefault_impl(quote! { except(Skipped) }, original);
+        let stripped = autodefault_impl(quote! { except(Skipped), strip }, filled);
+
+        assert_eq!(
+            stripped.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10 };
+                    let b = Foo { a: 10 };
+                    let c = Foo {};
+                    let d = Foo { a: 10, ..base };
+                    let e = Skipped { a: 10, ..Default::default() };
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn autodefault_match() {
+        let output = autodefault_match_impl(quote! {
+            event.kind(),
+            Kind::Click { x, .. } => Click { x },
+            Kind::Key(code) if code > 0 => { Key { code } }
+            _ => Other {},
+        });
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                    match event.kind() {
+                        Kind::Click { x, .. } => Click { x, ..::core::default::Default::default() },
+                        Kind::Key(code) if code > 0 => { Key { code, ..::core::default::Default::default() } }
+                        _ => Other { ..::core::default::Default::default() },
+                    }
+                }.to_string()
+        );
+
+        let output = autodefault_match_impl(quote! { x => Foo {} });
+        assert!(output.to_string().contains("compile_error"));
+    }
+
+    #[test]
+    fn let_else() {
+        let output = autodefault_impl(
+            quote! { except(Skipped) },
+            quote! {
+                fn demo() -> Bar {
+                    let Foo { a, .. } = get(Foo { a: 1 }) else {
+                        log(Baz {});
+                        return Bar { code: 1 };
+                    };
+
+                    let Some(x): Option<Skipped> = Some(Skipped {}) else { return Bar {} };
+
+                    Bar { code: a }
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                    fn demo() -> Bar {
+                        let Foo { a, .. } = get(Foo { a: 1, ..::core::default::Default::default() }) else {
+                            log(Baz { ..::core::default::Default::default() });
+                            return Bar { code: 1, ..::core::default::Default::default() };
+                        };
+
+                        let Some(x): Option<Skipped> = Some(Skipped {}) else {
+                            return Bar { ..::core::default::Default::default() }
+                        };
+
+                        Bar { code: a, ..::core::default::Default::default() }
+                    }
+                }.to_string()
+        );
+    }
+
+    #[test]
+    fn branches() {
+        let output = autodefault_impl(
+            TokenStream2::new(),
+            quote! {
+                fn demo(x: u8) -> Result<Foo, Error> {
+                    let a = match x {
+                        0 => Foo { a: 0 },
+                        1 => { Foo { a: 1 } }
+                        _ => return Ok(Foo { a: 2 }),
+                    };
+
+                    let b = if x > 0 { Foo { a: 3 } } else { Foo { a: 4 } };
+                    let c = Ok::<_, Error>(Foo { a: 5 })?;
+
+                    Ok(a)
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo(x: u8) -> Result<Foo, Error> {
+                    let a = match x {
+                        0 => Foo { a: 0, ..::core::default::Default::default() },
+                        1 => { Foo { a: 1, ..::core::default::Default::default() } }
+                        _ => return Ok(Foo { a: 2, ..::core::default::Default::default() }),
+                    };
+
+                    let b = if x > 0 {
+                        Foo { a: 3, ..::core::default::Default::default() }
+                    } else {
+                        Foo { a: 4, ..::core::default::Default::default() }
+                    };
+                    let c = Ok::<_, Error>(Foo { a: 5, ..::core::default::Default::default() })?;
+
+                    Ok(a)
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn return_only_branches() {
+        let output = autodefault_impl(
+            quote! { return_only },
+            quote! {
+                fn demo(x: u8) -> Foo {
+                    match (Key { x }) {
+                        Key { x: 0 } if (Check { x }).ok() => Foo { a: 0 },
+                        _ => if (Check { x }).ok() { Foo { a: 1 } } else { Foo { a: 2 } },
+                    }
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                    fn demo(x: u8) -> Foo {
+                        match (Key { x }) {
+                            Key { x: 0 } if (Check { x }).ok() => Foo { a: 0, ..::core::default::Default::default() },
+                            _ => if (Check { x }).ok() {
+                                Foo { a: 1, ..::core::default::Default::default() }
+                            } else {
+                                Foo { a: 2, ..::core::default::Default::default() }
+                            },
+                        }
+                    }
+                }.to_string()
+        );
+    }
+
+    #[test]
+    fn type_params() {
+        let output = autodefault_impl(
+            TokenStream2::new(),
+            quote! {
+                impl<U> Foo<U> {
+                    fn f<T>() {
+                        let _ = T {};
+                        let _ = U {};
+                        let _ = Bar {};
+                    }
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                impl<U> Foo<U> {
+                    fn f<T>() {
+                        let _ = T {};
+                        let _ = U {};
+                        let _ = Bar { ..::core::default::Default::default() };
+                    }
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn macros() {
+        let output = autodefault_impl(
+            quote! { macros(format, info, vec) },
+            quote! {
+                fn demo() {
+                    let a = format!("{} {x}", Foo { a: 1 }, x = Foo { a: 2 },);
+                    tracing::info!(target: "demo", value = ?Foo { a: 3 }, other.field = %Foo { a: 4 });
+                    let b = vec![Foo { a: 5 }; 3];
+                    let c = println!("{}", Foo { a: 6 });
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = format!(
+                        "{} {x}",
+                        Foo { a: 1, ..::core::default::Default::default() },
+                        x = Foo { a: 2, ..::core::default::Default::default() },
+                    );
+                    tracing::info!(
+                        target: "demo",
+                        value = ?Foo { a: 3, ..::core::default::Default::default() },
+                        other.field = %Foo { a: 4, ..::core::default::Default::default() }
+                    );
+                    let b = vec![Foo { a: 5 }; 3];
+                    let c = println!("{}", Foo { a: 6 });
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn type_params_scope() {
+        let output = autodefault_impl(
+            quote! { skip_generics },
+            quote! {
+                mod demo {
+                    fn generic<T>() {
+                        let make = |a| (Foo { a }, T {}, Wrapper::<T> { a });
+                    }
+
+                    fn concrete() {
+                        let make = |a| (Foo { a }, T {});
+                    }
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                mod demo {
+                    fn generic<T>() {
+                        let make = |a| (
+                            Foo { a, ..::core::default::Default::default() },
+                            T {},
+                            Wrapper::<T> { a }
+                        );
+                    }
+
+                    fn concrete() {
+                        let make = |a| (
+                            Foo { a, ..::core::default::Default::default() },
+                            T { ..::core::default::Default::default() }
+                        );
+                    }
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn strict_structs() {
+        let output = autodefault_impl(
+            quote! { strict_structs, only(Rect) },
+            quote! {
+                fn demo() {
+                    let a = Rect { w: 1 };
+                    let b = geometry::Rect { w: 1 };
+                    let c = Shape::Rect { w: 1 };
+                    let d = Self::Rect { w: 1 };
+                    let e = crate::shapes::Shape::Rect { w: 1 };
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Rect { w: 1, ..::core::default::Default::default() };
+                    let b = geometry::Rect { w: 1, ..::core::default::Default::default() };
+                    let c = Shape::Rect { w: 1 };
+                    let d = Self::Rect { w: 1 };
+                    let e = crate::shapes::Shape::Rect { w: 1 };
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn skip_generics() {
+        let input = quote! {
+            fn demo<T>(value: T) {
+                let a = Wrapper::<T> { value };
+                let b = Foo { a: 10 };
+            }
+        };
+
+        let output = autodefault_impl(quote! { skip_generics }, input.clone());
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo<T>(value: T) {
+                    let a = Wrapper::<T> { value };
+                    let b = Foo { a: 10, ..::core::default::Default::default() };
+                }
+            }
+            .to_string()
+        );
+
+        let output = autodefault_impl(TokenStream2::new(), input);
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo<T>(value: T) {
+                    let a = Wrapper::<T> { value, ..::core::default::Default::default() };
+                    let b = Foo { a: 10, ..::core::default::Default::default() };
+                }
+            }
+            .to_string()
+        )
+    }
+
+    #[test]
+    fn use_binding_type() {
+        let output = autodefault_impl(
+            quote! { only(Foo), use_binding_type },
+            quote! {
+                fn demo() {
+                    let a: Foo = Alias { a: 10 };
+                    let b: Bar = Foo { a: 10 };
+                    let c = Foo { a: 10 };
+                    let d: Foo = Alias { a: Foo {} };
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a: Foo = Alias { a: 10, ..::core::default::Default::default() };
+                    let b: Bar = Foo { a: 10 };
+                    let c = Foo { a: 10, ..::core::default::Default::default() };
+                    let d: Foo = Alias {
+                        a: Foo {..::core::default::Default::default()},
+                        ..::core::default::Default::default()
+                    };
+                }
+            }
+            .to_string()
+        )
+    }
+
+    #[test]
+    fn impl_trait_closures() {
+        let input = quote! {
+            fn demo(items: Vec<u32>) -> impl Iterator<Item = Foo> {
+                let first = items.iter().map(|&a| Foo { a }).next();
+
+                items
+                    .into_iter()
+                    .filter(|a| *a > 0)
+                    .map(|a| Foo { a })
+                    .chain(first)
+                    .chain(Some(0).map(|a| { Foo { a } }))
+            }
+        };
+
+        let output = autodefault_impl(TokenStream2::new(), input.clone());
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                    fn demo(items: Vec<u32>) -> impl Iterator<Item = Foo> {
+                        let first = items.iter().map(|&a| Foo { a, ..::core::default::Default::default() }).next();
+
+                        items
+                            .into_iter()
+                            .filter(|a| *a > 0)
+                            .map(|a| Foo { a, ..::core::default::Default::default() })
+                            .chain(first)
+                            .chain(Some(0).map(|a| { Foo { a, ..::core::default::Default::default() } }))
+                    }
+                }.to_string()
+        );
+
+        let output = autodefault_impl(quote! { return_only }, input);
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                    fn demo(items: Vec<u32>) -> impl Iterator<Item = Foo> {
+                        let first = items.iter().map(|&a| Foo { a }).next();
+
+                        items
+                            .into_iter()
+                            .filter(|a| *a > 0)
+                            .map(|a| Foo { a, ..::core::default::Default::default() })
+                            .chain(first)
+                            .chain(Some(0).map(|a| { Foo { a, ..::core::default::Default::default() } }))
+                    }
+                }.to_string()
+        );
+
+        // Inside those closures, only what the closure returns counts
+        let output = autodefault_impl(
+            quote! { return_only },
+            quote! {
+                fn demo(items: Vec<u32>) -> impl Iterator<Item = Foo> {
+                    items.into_iter().map(|a| Foo { a: count(Bar { b: a }) })
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo(items: Vec<u32>) -> impl Iterator<Item = Foo> {
+                    items.into_iter().map(|a| Foo {
+                        a: count(Bar { b: a }),
+                        ..::core::default::Default::default()
+                    })
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn bindings_only() {
+        let output = autodefault_impl(
+            quote! { bindings_only },
+            quote! {
+                fn demo() -> Foo {
+                    let a = Foo { a: Bar { b: 1 } };
+                    let b: Foo = (Foo { a: 1 });
+                    let c = [Foo { a: 1 }];
+                    takes(Foo { a: 1 });
+                    Foo { a: 1 }
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() -> Foo {
+                    let a = Foo { a: Bar { b: 1 }, ..::core::default::Default::default() };
+                    let b: Foo = (Foo { a: 1 });
+                    let c = [Foo { a: 1 }];
+                    takes(Foo { a: 1 });
+                    Foo { a: 1 }
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn return_only_operands() {
+        let output = autodefault_impl(
+            quote! { return_only },
+            quote! {
+                fn demo() -> Foo {
+                    if cond {
+                        return Foo { a: count(Bar { b: 3 }) };
+                    }
+
+                    Foo {
+                        a: make(Bar {}).scale(Bar {}),
+                        b: items[Bar {}.index] + Bar {}.offset,
+                        c: (Bar {}, [Bar {}]),
+                    }
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() -> Foo {
+                    if cond {
+                        return Foo {
+                            a: count(Bar { b: 3 }),
+                            ..::core::default::Default::default()
+                        };
+                    }
+
+                    Foo {
+                        a: make(Bar {}).scale(Bar {}),
+                        b: items[Bar {}.index] + Bar {}.offset,
+                        c: (
+                            Bar {..::core::default::Default::default()},
+                            [Bar {..::core::default::Default::default()}]
+                        ),
+                        ..::core::default::Default::default()
+                    }
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn return_only_constructors() {
+        let output = autodefault_impl(
+            quote! { return_only },
+            quote! {
+                fn demo() -> Result<Option<Foo>, Error> {
+                    if cond {
+                        return Err(Error::Invalid(Bar {}));
+                    }
+
+                    Ok(Some(Wrapper(Foo { a: make(Bar {}) })))
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() -> Result<Option<Foo>, Error> {
+                    if cond {
+                        return Err(Error::Invalid(Bar {..::core::default::Default::default()}));
+                    }
+
+                    Ok(Some(Wrapper(Foo {
+                        a: make(Bar {}),
+                        ..::core::default::Default::default()
+                    })))
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn return_only() {
+        let output = autodefault_impl(
+            quote! { return_only },
+            quote! {
+                fn demo() -> Foo {
+                    let a = Foo {};
+                    let f = |x| Foo {};
+
+                    if cond {
+                        return Foo { a: Bar {} };
+                    }
+
+                    match cond {
+                        true => Foo {},
+                        false => {
+                            let b = Foo {};
+                            Foo {}
+                        }
+                    }
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() -> Foo {
+                    let a = Foo {};
+                    let f = |x| Foo {};
+
+                    if cond {
+                        return Foo {
+                            a: Bar {..::core::default::Default::default()},
+                            ..::core::default::Default::default()
+                        };
+                    }
+
+                    match cond {
+                        true => Foo {..::core::default::Default::default()},
+                        false => {
+                            let b = Foo {};
+                            Foo {..::core::default::Default::default()}
+                        }
+                    }
+                }
+            }
+            .to_string()
+        )
+    }
+
+    #[test]
+    fn with() {
+        let output = autodefault_impl(
+            quote! { with = config::defaults },
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10 };
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10, ..config::defaults() };
+                }
+            }
+            .to_string()
+        )
+    }
+
+    #[test]
+    fn from() {
+        let input = quote! {
+            fn demo() {
+                let a = Foo { a: 10 };
+                let b = Bar { b: 10 };
+            }
+        };
+
+        let output = autodefault_impl(quote! { from = sane_defaults, except(Bar) }, input.clone());
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10, ..sane_defaults() };
+                    let b = Bar { b: 10 };
+                }
+            }
+            .to_string()
+        );
+
+        let output = autodefault_impl(quote! { from = sane_defaults, only(Bar), typed }, input);
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10 };
+                    let b = Bar { b: 10, ..::core::convert::identity::<Bar>(sane_defaults()) };
+                }
+            }
+            .to_string()
+        );
+
+        let output = autodefault_impl(quote! { from = path::to::func }, quote! { fn demo() {} });
+        assert!(output.to_string().contains("compile_error"));
+    }
+
+    #[test]
+    fn rest_tokens() {
+        let output = autodefault_impl(
+            quote! { rest_tokens("..MyDefault::<Foo>::build()") },
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10 };
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10, ..MyDefault::<Foo>::build() };
+                }
+            }
+            .to_string()
+        );
+
+        let output = autodefault_impl(
+            quote! { rest_tokens("..MyDefault::build(") },
+            quote! {
+                fn demo() {}
+            },
+        );
+
+        assert!(output.to_string().contains("compile_error"));
+    }
+
+    #[test]
+    fn type_fills() {
+        let output = autodefault_impl(
+            quote! { fill(Config = Config::prod(), Window = make_window(800, 600)) },
+            quote! {
+                fn demo() {
+                    let a = Config { retries: 3 };
+                    let b = Window { height: 400 };
+                    let c = Other {};
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Config { retries: 3, ..Config::prod() };
+                    let b = Window { height: 400, ..make_window(800, 600) };
+                    let c = Other {..::core::default::Default::default()};
+                }
+            }
+            .to_string()
+        )
+    }
+
+    #[test]
+    fn fill_only() {
+        let output = autodefault_impl(
+            quote! { fill(Config = Config::prod()), fill_only },
+            quote! {
+                fn demo() {
+                    let a = Config { retries: 3 };
+                    let c = Other {};
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Config { retries: 3, ..Config::prod() };
+                    let c = Other {};
+                }
+            }
+            .to_string()
+        )
+    }
+
+    #[test]
+    fn fields() {
+        let output = autodefault_impl(
+            quote! { field(id = Uuid::new_v4()) },
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10 };
+                    let b = Foo { id: 10 };
+                    let c = Foo {};
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                    fn demo() {
+                        let a = Foo { a: 10, id: Uuid::new_v4(), ..::core::default::Default::default() };
+                        let b = Foo { id: 10, ..::core::default::Default::default() };
+                        let c = Foo { id: Uuid::new_v4(), ..::core::default::Default::default() };
+                    }
+                }.to_string()
+        )
+    }
+
+    #[test]
+    fn variant_fields() {
+        let output = autodefault_impl(
+            quote! { only(Event::Click, Config), field(id = 0) },
+            quote! {
+                fn demo() {
+                    let a = Event::Click { x: 1 };
+                    let b = Event::Key { code: 13 };
+                    let c = Event::Click { x: 1, id: 7 };
+                    let d = Config {};
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Event::Click { x: 1, id: 0 };
+                    let b = Event::Key { code: 13 };
+                    let c = Event::Click { x: 1, id: 7 };
+                    let d = Config { id: 0, ..::core::default::Default::default() };
+                }
+            }
+            .to_string()
+        );
+
+        // Adding fields to a variant counts as an effect
+        let output = autodefault_impl(
+            quote! { only(Event::Click), field(id = 0), require_effect },
+            quote! {
+                fn demo() {
+                    let a = Event::Click { x: 1 };
+                }
+            },
+        );
+
+        assert!(!output.to_string().contains("compile_error"));
+    }
+
+    #[test]
+    fn fields_order() {
+        let output = autodefault_impl(
+            quote! { field(id = 1, rev = 2), field(id = 3) },
+            quote! {
+                fn demo() {
+                    let a = Foo { b: 1, a: 2 };
+                    let b = Foo { rev: 5, b: 1 };
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                    fn demo() {
+                        let a = Foo { b: 1, a: 2, id: 3, rev: 2, ..::core::default::Default::default() };
+                        let b = Foo { rev: 5, b: 1, id: 3, ..::core::default::Default::default() };
+                    }
+                }.to_string()
+        );
+    }
+
+    #[test]
+    fn fields_with_rest() {
+        let output = autodefault_impl(
+            quote! { field(id = Uuid::new_v4()) },
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10, ..Default::default() };
+                    let b = Foo { a: 10, ..custom() };
+                    let c = Foo { ..custom() };
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10, id: Uuid::new_v4(), ..Default::default() };
+                    let b = Foo { a: 10, id: Uuid::new_v4(), ..custom() };
+                    let c = Foo { id: Uuid::new_v4(), ..custom() };
+                }
+            }
+            .to_string()
+        );
+
+        let output = autodefault_impl(
+            quote! { field(id = Uuid::new_v4()), fields_first },
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10, ..custom() };
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Foo { id: Uuid::new_v4(), a: 10, ..custom() };
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn fields_first() {
+        let output = autodefault_impl(
+            quote! { field(id = next_id(), admin = false), fields_first },
+            quote! {
+                fn demo() {
+                    let a = User { name: name() };
+                    let b = User { name: name(), admin: true, };
+                    let c = User {};
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = User {
+                        id: next_id(),
+                        admin: false,
+                        name: name(),
+                        ..::core::default::Default::default()
+                    };
+                    let b = User {
+                        id: next_id(),
+                        name: name(),
+                        admin: true,
+                        ..::core::default::Default::default()
+                    };
+                    let c = User {
+                        id: next_id(),
+                        admin: false,
+                        ..::core::default::Default::default()
+                    };
+                }
+            }
+            .to_string()
+        )
+    }
+
+    #[test]
+    fn require_field() {
+        let output = autodefault_impl(
+            quote! { require_field(password) },
+            quote! {
+                fn demo(base: Login, password: String) {
+                    let a = Login { user: user(), password };
+                    let b = Login { user: user(), ..base };
+                }
+            },
+        );
+
+        assert!(!output.to_string().contains("compile_error"));
+
+        let output = autodefault_impl(
+            quote! { require_field(password) },
+            quote! {
+                fn demo() {
+                    let a = Login { user: user() };
+                }
+            },
+        );
+
+        assert!(output.to_string().contains("compile_error"));
+    }
+
+    #[test]
+    fn typed() {
+        let output = autodefault_impl(
+            quote! { typed },
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10 };
+                    let b = grid::Grid::<u8> {};
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                    fn demo() {
+                        let a = Foo { a: 10, ..<Foo as ::core::default::Default>::default() };
+                        let b = grid::Grid::<u8> {..<grid::Grid::<u8> as ::core::default::Default>::default()};
+                    }
+                }.to_string()
+        )
+    }
+
+    #[test]
+    fn path_std() {
+        let output = autodefault_impl(
+            quote! { path = std },
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10 };
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10, ..::std::default::Default::default() };
+                }
+            }
+            .to_string()
+        )
+    }
+
+    #[test]
+    fn path_custom() {
+        let output = autodefault_impl(
+            quote! { path = "my_prelude::Default" },
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10 };
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10, ..my_prelude::Default::default() };
+                }
+            }
+            .to_string()
+        )
+    }
+
+    #[test]
+    fn unqualified() {
+        let output = autodefault_impl(
+            quote! { unqualified },
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10 };
+                }
+            },
+        );
+
+        assert_ne!(
+            output.to_string(),
+            autodefault_impl(
+                TokenStream2::new(),
+                quote! {
+                    fn demo() {
+                        let a = Foo { a: 10 };
+                    }
+                }
+            )
+            .to_string()
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10, ..Default::default() };
+                }
+            }
+            .to_string()
+        )
+    }
+
+    #[test]
+    fn fill_conflicts() {
+        let fn_item = quote! { fn demo() {} };
+
+        for options in [
+            quote! { with = make, path = std },
+            quote! { inherent = new, fill_trait = Zeroable },
+            quote! { unqualified, fill_trait = my_crate::Default },
+            quote! { with = make, fill_method = zeroed },
+            quote! { fill_method = zeroed, rest_tokens("make()") },
+            quote! { from = make, with = other },
+        ] {
+            let output = autodefault_impl(options.clone(), fn_item.clone()).to_string();
+            assert!(output.contains("can't be combined with"), "{}", options);
+        }
+
+        for options in [
+            quote! { fill_trait = Zeroable, fill_method = zeroed },
+            quote! { fill_method = zeroed, path = std },
+            quote! { config(with = make), path = std },
+        ] {
+            let output = autodefault_impl(options.clone(), fn_item.clone()).to_string();
+            assert!(!output.contains("compile_error"), "{}", options);
+        }
+    }
+
+    #[test]
+    fn fill_trait() {
+        let input = quote! {
+            fn demo() {
+                let a = Foo { a: 10 };
+            }
+        };
+
+        let output = autodefault_impl(
+            quote! { fill_trait = bytemuck::Zeroable, fill_method = zeroed },
+            input.clone(),
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10, ..bytemuck::Zeroable::zeroed() };
+                }
+            }
+            .to_string()
+        );
+
+        let output = autodefault_impl(
+            quote! { fill_trait = bytemuck::Zeroable, fill_method = zeroed, typed },
+            input,
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10, ..<Foo as bytemuck::Zeroable>::zeroed() };
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn inherent() {
+        let input = quote! {
+            fn demo() {
+                let a = Foo { a: 10 };
+                let b = ui::Bar::<u8> { b: 10 };
+                let c = Baz { c: 10 };
+            }
+        };
+
+        let output = autodefault_impl(quote! { inherent = new }, input.clone());
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10, ..Foo::new() };
+                    let b = ui::Bar::<u8> { b: 10, ..ui::Bar::<u8>::new() };
+                    let c = Baz { c: 10, ..Baz::new() };
+                }
+            }
+            .to_string()
+        );
+
+        let output = autodefault_impl(quote! { inherent = new, except(Baz) }, input.clone());
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10, ..Foo::new() };
+                    let b = ui::Bar::<u8> { b: 10, ..ui::Bar::<u8>::new() };
+                    let c = Baz { c: 10 };
+                }
+            }
+            .to_string()
+        );
+
+        let output = autodefault_impl(quote! { inherent = empty, only(Foo) }, input);
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10, ..Foo::empty() };
+                    let b = ui::Bar::<u8> { b: 10 };
+                    let c = Baz { c: 10 };
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn template() {
+        let output = autodefault_impl(
+            quote! { template(Config { retries: 3, timeout: secs(30) }) },
+            quote! {
+                fn demo() {
+                    let a = Config { verbose: true };
+                    let b = config::Config { timeout: secs(5) };
+                    let c = Config { verbose: true, ..base };
+                    let d = Other { a: 1 };
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Config { verbose: true, retries: 3, timeout: secs(30) };
+                    let b = config::Config { timeout: secs(5), retries: 3 };
+                    let c = Config { verbose: true, ..base };
+                    let d = Other { a: 1, ..::core::default::Default::default() };
+                }
+            }
+            .to_string()
+        );
+
+        let output = autodefault_impl(
+            quote! { template(Config { retries: 3, ..base }) },
+            quote! {
+                fn demo() {}
+            },
+        );
+
+        assert!(output.to_string().contains("compile_error"));
+
+        // Struct expressions in the template are filled too
+        let output = autodefault_impl(
+            quote! { template(Outer { inner: Inner { x: 5 }, z: 1 }) },
+            quote! {
+                fn demo() {
+                    let a = Outer { name: "a", z: 2 };
+                    let b = Outer { z: 3 };
+                }
+            },
+        );
+
+        let expected = quote! {
+            fn demo() {
+                let a = Outer {
+                    name: "a",
+                    z: 2,
+                    inner: Inner { x: 5, ..::core::default::Default::default() }
+                };
+                let b = Outer {
+                    z: 3,
+                    inner: Inner { x: 5, ..::core::default::Default::default() }
+                };
+            }
+        };
+
+        assert_eq!(output.to_string(), expected.to_string());
+
+        // Fields from `require_field` must still be set explicitly
+        let output = autodefault_impl(
+            quote! { template(Config { name: "default" }), require_field(name) },
+            quote! {
+                fn demo() {
+                    let a = Config { name: "a" };
+                    let b = Config { retries: 3 };
+                }
+            },
+        )
+        .to_string();
+
+        assert_eq!(output.matches("'name' must be set explicitly").count(), 1);
+
+        // Filling from a template counts as an effect
+        let output = autodefault_impl(
+            quote! { template(Config { retries: 3 }), require_effect, count_const = FILLED },
+            quote! {
+                fn demo() {
+                    let a = Config { verbose: true };
+                    let b = Config { retries: 5 };
+                }
+            },
+        )
+        .to_string();
+
+        assert!(!output.contains("compile_error"), "{}", output);
+        assert!(output.contains("const FILLED : usize = 1 ;"), "{}", output);
+    }
+
+    #[test]
+    fn const_default() {
+        let output = autodefault_impl(
+            quote! { const_default = Foo::const_default },
+            quote! {
+                mod demo {
+                    const fn demo1() {
+                        let a = Foo { a: 10 };
+                    }
+
+                    fn demo2() {
+                        let a = Foo { a: 10 };
+                    }
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                mod demo {
+                    const fn demo1() {
+                        let a = Foo { a: 10, ..Foo::const_default() };
+                    }
+
+                    fn demo2() {
+                        let a = Foo { a: 10, ..::core::default::Default::default() };
+                    }
+                }
+            }
+            .to_string()
+        )
+    }
+
+    #[test]
+    fn array_repeat() {
+        let input = quote! {
+            fn demo() {
+                let a = [Foo { x: 1 }; 4];
+                let b = [0; Foo {}.len];
+            }
+        };
+
+        let output = autodefault_impl(quote! { const_default = Foo::new }, input.clone());
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = [Foo { x: 1, ..::core::default::Default::default() }; 4];
+                    let b = [0; Foo { ..Foo::new() }.len];
+                }
+            }
+            .to_string()
+        );
+
+        let output = autodefault_impl(TokenStream2::new(), input);
+        assert!(output.to_string().contains("compile_error"));
+    }
+
+    #[test]
+    fn const_fn_without_const_default() {
+        let output = autodefault_impl(
+            TokenStream2::new(),
+            quote! {
+                const fn demo() {
+                    let a = Foo { a: 10 };
+                }
+            },
+        );
+
+        assert!(output.to_string().contains("compile_error"));
+    }
+
+    #[test]
+    fn const_arguments() {
+        let input = quote! {
+            fn demo() {
+                let a = make::<{ Foo { a: 1 } }>();
+                let b: Grid<{ Foo { a: 1 } }> = Grid::<{ Foo { a: 1 } }>::new();
+            }
+        };
+
+        let output = autodefault_impl(TokenStream2::new(), input.clone());
+        assert_eq!(output.to_string(), input.to_string());
+
+        let output = autodefault_impl(quote! { const_default = Foo::new }, input);
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = make::<{ Foo { a: 1, ..Foo::new() } }>();
+                    let b: Grid<{ Foo { a: 1, ..Foo::new() } }> =
+                        Grid::<{ Foo { a: 1, ..Foo::new() } }>::new();
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn verbatim() {
+        let input = quote! {
+            fn demo() {
+                let a = autodefault::verbatim! { Foo { a: 1 } };
+                verbatim! {
+                    let b = Bar { b: Foo { a: 2 } };
+                }
+            }
+        };
+
+        let output = autodefault_impl(quote! { macros(verbatim) }, input.clone());
+        assert_eq!(output.to_string(), input.to_string());
+    }
+
+    #[test]
+    fn require_effect() {
+        let output = autodefault_impl(
+            quote! { require_effect },
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10 };
+                }
+            },
+        );
+
+        assert!(!output.to_string().contains("compile_error"));
+
+        let output = autodefault_impl(
+            quote! { require_effect },
+            quote! {
+                fn demo(base: Foo) {
+                    let a = Foo { a: 10, ..base };
+                    let b = 3;
+                }
+            },
+        );
+
+        assert!(output.to_string().contains("compile_error"));
+        assert!(output.to_string().contains("fn demo"));
+
+        let output = autodefault_impl(
+            quote! { strip, require_effect },
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10, ..Default::default() };
+                }
+            },
+        );
+
+        assert!(output
+            .to_string()
+            .contains("'strip' can't be combined with 'require_effect'"));
+    }
+
+    #[test]
+    fn ignore_unit_like() {
+        let output = autodefault_impl(
+            quote! { ignore_unit_like(Marker, Tag) },
+            quote! {
+                fn demo() {
+                    let a = Marker {};
+                    let b = Foo { marker: markers::Marker {}, tag: Tag { ..tag } };
+                    let c = #[autodefault(skip)] Tag {};
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Marker;
+                    let b = Foo {
+                        marker: markers::Marker,
+                        tag: Tag { ..tag },
+                        ..::core::default::Default::default()
+                    };
+                    let c = Tag {};
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn exhaustive() {
+        let output = autodefault_impl(
+            quote! { exhaustive },
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10 };
+                }
+            },
+        );
+
+        assert!(!output.to_string().contains("compile_error"));
+
+        let output = autodefault_impl(
+            quote! { exhaustive },
+            quote! {
+                fn demo(base: Foo) {
+                    let a = Foo { a: 10, ..base };
+                }
+            },
+        );
+
+        assert!(output.to_string().contains("compile_error"));
+    }
+
+    #[test]
+    fn block_rules() {
+        let output = autodefault_impl(
+            quote! { except(Bar) },
+            quote! {
+                fn demo() {
+                    let a = Foo {};
+
+                    #[autodefault(only(Bar))]
+                    {
+                        let b = Foo {};
+                        let c = Bar {};
+                    }
+
+                    let d = Bar {};
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Foo { ..::core::default::Default::default() };
+
+                    {
+                        let b = Foo {};
+                        let c = Bar { ..::core::default::Default::default() };
+                    }
+
+                    let d = Bar {};
+                }
+            }
+            .to_string()
+        );
+
+        let output = autodefault_impl(
+            TokenStream2::new(),
+            quote! {
+                fn demo() {
+                    #[autodefault(typed)]
+                    {
+                        let b = Foo {};
+                    }
+                }
+            },
+        );
+
+        assert!(output.to_string().contains("compile_error"));
+    }
+
+    #[test]
+    fn strip_redundant() {
+        let output = autodefault_impl(
+            quote! { strip_redundant, typed },
+            quote! {
+                fn demo(base: Foo) {
+                    let a = Foo { a: 10, ..Default::default() };
+                    let b = Foo { a: 10, ..std::default::Default::default() };
+                    let c = Foo { ..<Foo as Default>::default() };
+                    let d = Foo { a: 10, ..base };
+                    let e = Foo { a: 10, ..Foo::new() };
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo(base: Foo) {
+                    let a = Foo { a: 10, ..<Foo as ::core::default::Default>::default() };
+                    let b = Foo { a: 10, ..<Foo as ::core::default::Default>::default() };
+                    let c = Foo { ..<Foo as ::core::default::Default>::default() };
+                    let d = Foo { a: 10, ..base };
+                    let e = Foo { a: 10, ..Foo::new() };
+                }
+            }
+            .to_string()
+        )
+    }
+
+    #[test]
+    fn let_rules() {
+        let output = autodefault_impl(
+            quote! { except(Foo) },
+            quote! {
+                fn demo() {
+                    #[autodefault]
+                    let f = |x| Foo { a: x };
+
+                    #[autodefault(only(Bar))]
+                    let g = Bar { b: Foo {} };
+
+                    let h = Foo {};
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let f = |x| Foo { a: x, ..::core::default::Default::default() };
+
+                    let g = Bar { b: Foo {}, ..::core::default::Default::default() };
+
+                    let h = Foo {};
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn standalone_let() {
+        let output = autodefault_impl(
+            TokenStream2::new(),
+            quote! {
+                let f = |x| Foo { a: x };
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                let f = |x| Foo { a: x, ..::core::default::Default::default() };
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn skip_marker() {
+        let output = autodefault_impl(
+            TokenStream2::new(),
+            quote! {
+                fn demo() {
+                    let a = #[autodefault(skip)] Foo { a: 10 };
+                    let b = #[autodefault(skip)] Foo { a: Bar {} };
+                    call(#[autodefault(skip)] Foo { a: 10 }, Foo {});
+                    let c = #[allow(unused)] Foo {};
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10 };
+                    let b = Foo { a: Bar {..::core::default::Default::default()} };
+                    call(Foo { a: 10 }, Foo {..::core::default::Default::default()});
+                    let c = #[allow(unused)] Foo {..::core::default::Default::default()};
+                }
+            }
+            .to_string()
+        )
+    }
+
+    #[test]
+    fn checked() {
+        let output = autodefault_impl(
+            quote! { checked },
+            quote! {
+                fn demo<T>() {
+                    let a = Foo { a: 10 };
+                    let b = Grid { width: 3 };
+                    let c = Grid::<T> { width: 3 };
+                    let d = bar::Bar { b: Self {} };
+                    let e = Foo { a: 11 };
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo<T>() {
+                    let a = Foo {
+                        a: 10,
+                        ..{
+                            fn assert_default<T: ::core::default::Default>() -> T {
+                                <T as ::core::default::Default>::default()
+                            }
+
+                            assert_default()
+                        }
+                    };
+                    let b = Grid {
+                        width: 3,
+                        ..{
+                            fn assert_default<T: ::core::default::Default>() -> T {
+                                <T as ::core::default::Default>::default()
+                            }
+
+                            assert_default()
+                        }
+                    };
+                    let c = Grid::<T> {
+                        width: 3,
+                        ..{
+                            fn assert_default<T: ::core::default::Default>() -> T {
+                                <T as ::core::default::Default>::default()
+                            }
+
+                            assert_default()
+                        }
+                    };
+                    let d = bar::Bar {
+                        b: Self {
+                            ..{
+                                fn assert_default<T: ::core::default::Default>() -> T {
+                                    <T as ::core::default::Default>::default()
+                                }
+
+                                assert_default()
+                            }
+                        },
+                        ..{
+                            fn assert_default<T: ::core::default::Default>() -> T {
+                                <T as ::core::default::Default>::default()
+                            }
+
+                            assert_default()
+                        }
+                    };
+                    let e = Foo { a: 11, ..::core::default::Default::default() };
+                }
+            }
+            .to_string()
+        );
+
+        // With `typed`, the asserted type is named explicitly
+        let output = autodefault_impl(
+            quote! { checked, typed },
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10 };
+                    let b = Foo { a: 11 };
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Foo {
+                        a: 10,
+                        ..{
+                            fn assert_default<T: ::core::default::Default>() -> T {
+                                <T as ::core::default::Default>::default()
+                            }
+
+                            assert_default::<Foo>()
+                        }
+                    };
+                    let b = Foo { a: 11, ..<Foo as ::core::default::Default>::default() };
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn allow_qualification() {
+        let output = autodefault_impl(
+            quote! { allow_qualification },
+            quote! {
+                mod demo {
+                    fn demo1() {}
+
+                    #[allow(unused_qualifications)]
+                    fn demo2() {}
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                mod demo {
+                    #[allow(unused_qualifications)]
+                    fn demo1() {}
+
+                    #[allow(unused_qualifications)]
+                    fn demo2() {}
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn allow_clippy() {
+        let output = autodefault_impl(
+            quote! { allow_clippy },
+            quote! {
+                mod demo {
+                    #[inline]
+                    fn demo1() {
+                        fn inner() {}
+                        let a = Foo { a: 10 };
+                    }
+
+                    #[allow(clippy::default_trait_access)]
+                    fn demo2() {}
+
+                    #[allow(dead_code, clippy::default_trait_access)]
+                    fn demo3() {}
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                mod demo {
+                    #[allow(clippy::default_trait_access)]
+                    #[inline]
+                    fn demo1() {
+                        fn inner() {}
+                        let a = Foo { a: 10, ..::core::default::Default::default() };
+                    }
+
+                    #[allow(clippy::default_trait_access)]
+                    fn demo2() {}
+
+                    #[allow(dead_code, clippy::default_trait_access)]
+                    fn demo3() {}
+                }
+            }
+            .to_string()
+        )
+    }
+
+    #[test]
+    fn debug() {
+        let input = quote! {
+            fn demo() {
+                let a = Foo { a: 10 };
+            }
+        };
+
+        assert_eq!(
+            autodefault_impl(quote! { debug }, input.clone()).to_string(),
+            autodefault_impl(TokenStream2::new(), input).to_string(),
+        )
+    }
+
+    #[test]
+    fn dry_run() {
+        let input = quote! {
+            fn demo() {
+                let a = Foo { a: 10 };
+            }
+        };
+
+        assert_eq!(
+            autodefault_impl(quote! { dry_run }, input.clone()).to_string(),
+            input.to_string(),
+        );
+
+        // The crate's own markers are still removed
+        let output = autodefault_impl(
+            quote! { dry_run },
+            quote! {
+                fn demo() {
+                    let a = #[autodefault(skip)] Foo { a: 10 };
+                    #[autodefault(only(Bar))]
+                    let b = Foo { a: 10 };
+                    let c = #[autodefault] { Foo { a: 10 } };
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10 };
+                    let b = Foo { a: 10 };
+                    let c = { Foo { a: 10 } };
+                }
+            }
+            .to_string()
+        );
+
+        // Errors that a real run would report are still reported
+        let output = autodefault_impl(
+            quote! { dry_run, require_field(b), except(Unused), check_filters },
+            input,
+        )
+        .to_string();
+
+        assert!(
+            output.contains("The field 'b' must be set explicitly"),
+            "{}",
+            output
+        );
+        assert!(output.contains("'Unused' didn't match"), "{}", output);
+    }
+
+    #[test]
+    fn max_depth() {
+        let output = autodefault_impl(
+            quote! { max_depth = 2 },
+            quote! {
+                fn demo() {
+                    let a = Outer { middle: Middle { leaf: Leaf { a: 1 } } };
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Outer {
+                        middle: Middle {
+                            leaf: Leaf { a: 1 },
+                            ..::core::default::Default::default()
+                        },
+                        ..::core::default::Default::default()
+                    };
+                }
+            }
+            .to_string()
+        )
+    }
+
+    #[test]
+    fn outermost() {
+        let output = autodefault_impl(
+            quote! { outermost },
+            quote! {
+                fn demo() {
+                    let a = Bundle { sprite: Sprite { size: 1 } };
+                    let b = Sprite {};
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Bundle {
+                        sprite: Sprite { size: 1 },
+                        ..::core::default::Default::default()
+                    };
+                    let b = Sprite { ..::core::default::Default::default() };
+                }
+            }
+            .to_string()
+        )
+    }
+
+    #[test]
+    fn innermost() {
+        let output = autodefault_impl(
+            quote! { innermost },
+            quote! {
+                fn demo() {
+                    let a = Bundle { sprite: Sprite { size: 1 } };
+                    let b = Sprite {};
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Bundle {
+                        sprite: Sprite { size: 1, ..::core::default::Default::default() }
+                    };
+                    let b = Sprite { ..::core::default::Default::default() };
+                }
+            }
+            .to_string()
+        )
+    }
+
+    #[test]
+    fn unparsable_item() {
+        let input = quote! {
+            fn demo() {
+                let x = Foo { a: 10, b: };
+            }
+        };
+        let output = autodefault_impl(TokenStream2::new(), input.clone()).to_string();
+
+        assert!(output.starts_with(&input.to_string()));
+        assert!(output.contains("compile_error"));
+    }
+
+    #[test]
+    fn summary() {
+        let filled: Vec<(String, Span, String)> = ["Foo", "Bar", "Foo", "Baz", "Bar", "Foo"]
+            .iter()
+            .map(|name| (name.to_string(), Span::call_site(), String::new()))
+            .collect();
+
+        assert_eq!(
+            summarize(&filled),
+            "autodefault: filled 6 literals across 3 types: Foo(3), Bar(2), Baz(1)"
+        );
+    }
+
+    #[test]
+    fn recursion_limit() {
+        let nested = |depth: usize| -> TokenStream2 {
+            let expr = (0..depth).fold("Foo { a: 1 }".to_owned(), |expr, _| {
+                format!("Foo {{ a: 1, b: {} }}", expr)
+            });
+
+            format!("fn demo() {{ let x = {}; }}", expr)
+                .parse()
+                .unwrap()
+        };
+
+        let output = autodefault_impl(TokenStream2::new(), nested(500));
+        assert!(output.to_string().contains("compile_error"));
+
+        let output = autodefault_impl(TokenStream2::new(), nested(20));
+        assert!(!output.to_string().contains("compile_error"));
+
+        let output = autodefault_impl(quote! { recursion_limit = 10 }, nested(20));
+        assert!(output.to_string().contains("compile_error"));
+    }
+
+    #[test]
+    fn fn_attributes() {
+        let output = autodefault_impl(
+            TokenStream2::new(),
+            quote! {
+                /// Build a `Foo`
+                #[inline]
+                #[must_use = "this returns a new Foo"]
+                #[cfg_attr(test, allow(dead_code))]
+                /// More docs
+                pub fn demo() -> Foo {
+                    Foo { a: 10 }
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                /// Build a `Foo`
+                #[inline]
+                #[must_use = "this returns a new Foo"]
+                #[cfg_attr(test, allow(dead_code))]
+                /// More docs
+                pub fn demo() -> Foo {
+                    Foo { a: 10, ..::core::default::Default::default() }
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn collections() {
+        let output = autodefault_impl(
+            TokenStream2::new(),
+            quote! {
+                fn demo() {
+                    let a = (Foo { a: 1 }, Bar { b: 2 });
+                    let b = [Foo { a: 1 }, Foo { a: 2 }];
+                    let c = [(Foo { a: 1 }, [Bar { b: 2 }]), (Foo { a: 3 }, [Bar { b: 4 }])];
+                    let d = Foo { a: (Bar { b: 1 }, [Bar { b: 2 }]) };
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = (
+                        Foo { a: 1, ..::core::default::Default::default() },
+                        Bar { b: 2, ..::core::default::Default::default() }
+                    );
+                    let b = [
+                        Foo { a: 1, ..::core::default::Default::default() },
+                        Foo { a: 2, ..::core::default::Default::default() }
+                    ];
+                    let c = [
+                        (
+                            Foo { a: 1, ..::core::default::Default::default() },
+                            [Bar { b: 2, ..::core::default::Default::default() }]
+                        ),
+                        (
+                            Foo { a: 3, ..::core::default::Default::default() },
+                            [Bar { b: 4, ..::core::default::Default::default() }]
+                        )
+                    ];
+                    let d = Foo {
+                        a: (
+                            Bar { b: 1, ..::core::default::Default::default() },
+                            [Bar { b: 2, ..::core::default::Default::default() }]
+                        ),
+                        ..::core::default::Default::default()
+                    };
+                }
+            }
+            .to_string()
+        );
+
+        let output = autodefault_impl(
+            quote! { return_only },
+            quote! {
+                fn demo() -> (Foo, [Bar; 1]) {
+                    let a = (Foo { a: 1 }, [Bar { b: 2 }]);
+                    (Foo { a: 1 }, [Bar { b: 2 }])
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() -> (Foo, [Bar; 1]) {
+                    let a = (Foo { a: 1 }, [Bar { b: 2 }]);
+                    (
+                        Foo { a: 1, ..::core::default::Default::default() },
+                        [Bar { b: 2, ..::core::default::Default::default() }]
+                    )
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn wrapped_arguments() {
+        let output = autodefault_impl(
+            TokenStream2::new(),
+            quote! {
+                fn demo() {
+                    render(&Foo {});
+                    update(&mut Foo { a: 1 });
+                    let b = Box::new(Foo { a: 1 });
+                    let c = Rc::new(Foo { a: 1 });
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    render(&Foo { ..::core::default::Default::default() });
+                    update(&mut Foo { a: 1, ..::core::default::Default::default() });
+                    let b = Box::new(Foo { a: 1, ..::core::default::Default::default() });
+                    let c = Rc::new(Foo { a: 1, ..::core::default::Default::default() });
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn errors_without_markers() {
+        let input = quote! {
+            fn demo() {
+                let a = #[autodefault(skip)] Foo { a: 1 };
+                #[autodefault(only(Bar))]
+                let b = Foo { a: 2 };
+                let c = Foo { a: 3 };
+            }
+        };
+
+        for attr in [quote! { not_an_option }, quote! { max_literals = 0 }] {
+            let output = autodefault_impl(attr, input.clone()).to_string();
+
+            assert!(output.contains("compile_error"), "{}", output);
+            assert!(!output.contains("# [autodefault"), "{}", output);
+        }
+    }
+
+    #[test]
+    fn max_literals() {
+        let input = quote! {
+            fn demo() {
+                let a = Foo { a: 1 };
+                let b = Foo { a: 2 };
+                let c = Foo { a: 3, ..base };
+            }
+        };
+
+        let output = autodefault_impl(quote! { max_literals = 2 }, input.clone());
+        assert!(!output.to_string().contains("compile_error"));
+
+        let output = autodefault_impl(quote! { max_literals = 1 }, input);
+        let output = output.to_string();
+        assert!(output.contains("compile_error"));
+        assert!(output.contains("has 2 struct expressions to fill"));
+        assert!(output.contains("limit of 1"));
+        assert!(!output.contains("Default"));
+    }
+
+    #[test]
+    fn count_const() {
+        let output = autodefault_impl(
+            quote! { count_const = DEMO_FILLS },
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10 };
+                    let b = Foo { a: 10, ..a };
+                    let c = Foo { b: Bar {} };
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Foo { a: 10, ..::core::default::Default::default() };
+                    let b = Foo { a: 10, ..a };
+                    let c = Foo {
+                        b: Bar { ..::core::default::Default::default() },
+                        ..::core::default::Default::default()
+                    };
+                }
+
+                #[allow(dead_code)]
+                const DEMO_FILLS: usize = 3;
+            }
+            .to_string()
+        )
+    }
+
+    #[test]
+    fn only_derive() {
+        let output = autodefault_impl(
+            quote! { only_derive },
+            quote! {
+                fn demo() {
+                    #[derive(Debug, Default)]
+                    struct Local1 {
+                        a: i32,
+                    }
+
+                    #[derive(Clone, std::default::Default)]
+                    struct Local2 {
+                        a: i32,
+                    }
+
+                    struct Local3 {
+                        a: i32,
+                    }
+
+                    let a = Local1 {};
+                    let b = Local2 {};
+                    let c = Local3 { a: 10 };
+                    let d = Remote {};
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    #[derive(Debug, Default)]
+                    struct Local1 {
+                        a: i32,
+                    }
+
+                    #[derive(Clone, std::default::Default)]
+                    struct Local2 {
+                        a: i32,
+                    }
+
+                    struct Local3 {
+                        a: i32,
+                    }
+
+                    let a = Local1 {..::core::default::Default::default()};
+                    let b = Local2 {..::core::default::Default::default()};
+                    let c = Local3 { a: 10 };
+                    let d = Remote {};
+                }
+            }
+            .to_string()
+        )
+    }
+
+    #[test]
+    fn module() {
+        let output = autodefault_impl(
+            quote! { only_derive },
+            quote! {
+                mod demo {
+                    #[derive(Default)]
+                    struct Foo {
+                        a: i32,
+                    }
+
+                    struct Bar {
+                        foo: Foo,
+                    }
+
+                    fn demo1() -> Bar {
+                        Bar { foo: Foo {} }
+                    }
+
+                    fn demo2() -> Foo {
+                        Foo { a: 10 }
+                    }
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                mod demo {
+                    #[derive(Default)]
+                    struct Foo {
+                        a: i32,
+                    }
+
+                    struct Bar {
+                        foo: Foo,
+                    }
+
+                    fn demo1() -> Bar {
+                        Bar { foo: Foo {..::core::default::Default::default()} }
+                    }
+
+                    fn demo2() -> Foo {
+                        Foo { a: 10, ..::core::default::Default::default() }
+                    }
+                }
+            }
+            .to_string()
+        );
+
+        // Methods of impl blocks and traits in the module are processed too
+        let output = autodefault_impl(
+            quote! { only_derive },
+            quote! {
+                mod demo {
+                    #[derive(Default)]
+                    struct Foo {
+                        a: i32,
+                    }
+
+                    impl Foo {
+                        fn new() -> Self {
+                            Self { a: 1 }
+                        }
+                    }
+
+                    trait Make {
+                        fn make() -> Foo {
+                            Foo { a: 2 }
+                        }
+                    }
+
+                    fn demo() -> Bar {
+                        Bar { a: 3 }
+                    }
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                mod demo {
+                    #[derive(Default)]
+                    struct Foo {
+                        a: i32,
+                    }
+
+                    impl Foo {
+                        fn new() -> Self {
+                            Self { a: 1, ..::core::default::Default::default() }
+                        }
+                    }
+
+                    trait Make {
+                        fn make() -> Foo {
+                            Foo { a: 2, ..::core::default::Default::default() }
+                        }
+                    }
+
+                    fn demo() -> Bar {
+                        Bar { a: 3 }
+                    }
+                }
+            }
+            .to_string()
+        )
+    }
+
+    #[test]
+    fn rules() {
+        let input = quote! {
+            fn demo() {
+                let a = ui::Button { a: 1 };
+                let b = ui::RawHandle { a: 1 };
+                let c = net::Socket { a: 1 };
+                let d = net::Packet { a: 1 };
+                let e = Other { a: 1 };
+            }
+        };
+
+        let filled = |attr: TokenStream2| {
+            let output = autodefault_impl(attr, input.clone());
+            let output = output.to_string();
+            assert!(!output.contains("compile_error"), "{}", output);
+
+            ["Button", "RawHandle", "Socket", "Packet", "Other"]
+                .iter()
+                .filter(|name| output.contains(&format!("{} {{ a : 1 , ..", name)))
+                .copied()
+                .collect::<Vec<_>>()
+        };
+
+        // Starting with `only`, nothing else is filled
+        assert_eq!(
+            filled(quote! { rules(only(ui::*), except(ui::RawHandle), only(net::Socket)) }),
+            ["Button", "Socket"]
+        );
+
+        // Starting with `except`, everything else is filled
+        assert_eq!(
+            filled(quote! { rules(except(net::*), only(Socket), except(Button)) }),
+            ["RawHandle", "Socket", "Other"]
+        );
+
+        // The last matching clause wins
+        assert_eq!(
+            filled(quote! { rules(only(ui::*), except(ui::*), only(Button)) }),
+            ["Button"]
+        );
+
+        // A single clause is the same as plain `only` or `except`
+        assert_eq!(
+            filled(quote! { rules(only(Other)) }),
+            filled(quote! { only(Other) })
+        );
+        assert_eq!(
+            filled(quote! { rules(except_from(["net::*"])) }),
+            filled(quote! { except(net::*) })
+        );
+
+        // Clauses given in several `rules` are combined in order
+        assert_eq!(
+            filled(quote! { rules(only(ui::*)), rules(except(RawHandle)) }),
+            ["Button"]
+        );
+
+        let output = autodefault_impl(quote! { rules(only(Button)), except(Other) }, input.clone());
+        assert!(output.to_string().contains("compile_error"));
+
+        let output = autodefault_impl(quote! { rules(fill(Button)) }, input);
+        assert!(output.to_string().contains("compile_error"));
+    }
+
+    #[test]
+    fn leading_colon() {
+        let input = quote! {
+            fn demo() {
+                let a = ::a::b::Foo { a: 1 };
+                let b = a::b::Foo { a: 1 };
+                let c = ::c::Foo { a: 1 };
+            }
+        };
+
+        let expected = quote! {
+            fn demo() {
+                let a = ::a::b::Foo { a: 1, ..::core::default::Default::default() };
+                let b = a::b::Foo { a: 1, ..::core::default::Default::default() };
+                let c = ::c::Foo { a: 1 };
+            }
+        };
+
+        for filter in [
+            quote! { only(a::b::Foo) },
+            quote! { only(::a::b::Foo) },
+            quote! { only(a::*) },
+            quote! { except(::c::*) },
+        ] {
+            let output = autodefault_impl(filter, input.clone());
+            assert_eq!(output.to_string(), expected.to_string());
+        }
+
+        let output = autodefault_impl(quote! { only(Foo) }, input);
+        assert_eq!(output.to_string().matches("Default").count(), 3);
+    }
+
+    #[test]
+    fn impl_self() {
+        let input = quote! {
+            impl Widget {
+                fn demo() {
+                    let a = Self { a: 1 };
+                    let b = Widget { a: 1 };
+                    let c = crate::ui::Widget { a: 1 };
+                    let d = Other { a: 1 };
+                }
+            }
+        };
+
+        let expected = quote! {
+            impl Widget {
+                fn demo() {
+                    let a = Self { a: 1, ..::core::default::Default::default() };
+                    let b = Widget { a: 1, ..::core::default::Default::default() };
+                    let c = crate::ui::Widget { a: 1, ..::core::default::Default::default() };
+                    let d = Other { a: 1 };
+                }
+            }
+        };
+
+        let output = autodefault_impl(quote! { only(Widget) }, input.clone());
+        assert_eq!(output.to_string(), expected.to_string());
+
+        let output = autodefault_impl(quote! { only(Self) }, input);
+        assert_eq!(output.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn trait_defaults() {
+        let output = autodefault_impl(
+            TokenStream2::new(),
+            quote! {
+                trait Widget<T> {
+                    fn required(&self) -> Foo;
+
+                    fn provided(&self) -> Foo {
+                        let t = T {};
+                        Foo { a: 10 }
+                    }
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                trait Widget<T> {
+                    fn required(&self) -> Foo;
+
+                    fn provided(&self) -> Foo {
+                        let t = T {};
+                        Foo { a: 10, ..::core::default::Default::default() }
+                    }
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn invalid_items() {
+        for item in [
+            quote! { struct Foo { a: i32 } },
+            quote! { extern "C" { fn demo(); } },
+        ] {
+            let output = autodefault_impl(TokenStream2::new(), item.clone());
+            let output = output.to_string();
+
+            assert!(output.starts_with(&item.to_string()));
+            assert!(output.contains("compile_error"), "{}", output);
+            assert!(output.contains("can only be applied to functions"));
+        }
+    }
+
+    #[test]
+    fn impl_block() {
+        let output = autodefault_impl(
+            TokenStream2::new(),
+            quote! {
+                impl Foo {
+                    const X: i32 = 10;
+
+                    fn new() -> Self {
+                        Self { a: 10 }
+                    }
+
+                    fn bar(&self) -> Bar {
+                        Bar {}
+                    }
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                impl Foo {
+                    const X: i32 = 10;
+
+                    fn new() -> Self {
+                        Self { a: 10, ..::core::default::Default::default() }
+                    }
+
+                    fn bar(&self) -> Bar {
+                        Bar { ..::core::default::Default::default() }
+                    }
+                }
+            }
+            .to_string()
+        )
+    }
+
+    #[test]
+    fn only_fns_except_fns() {
+        let input = quote! {
+            mod demo {
+                fn a() { let x = Foo {}; }
+                fn b() { let x = Foo {}; }
+                fn c() { let x = #[autodefault(skip)] Foo {}; }
+            }
+        };
+
+        let output = autodefault_impl(quote! { only_fns(a, b), except_fns(b) }, input);
+
+        // Functions that aren't selected still have their markers removed
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                mod demo {
+                    fn a() { let x = Foo { ..::core::default::Default::default() }; }
+                    fn b() { let x = Foo {}; }
+                    fn c() { let x = Foo {}; }
+                }
+            }
+            .to_string()
+        )
+    }
+
+    #[test]
+    fn config() {
+        let output = config_impl(
+            quote! { except(Bar), typed },
+            quote! {
+                mod demo {
+                    #[autodefault]
+                    fn a() {}
+
+                    #[autodefault::autodefault(only(Baz))]
+                    fn b() {}
+
+                    impl Foo {
+                        #[autodefault]
+                        fn c() {}
+                    }
+
+                    #[autodefault]
+                    trait Qux {
+                        #[autodefault]
+                        fn e() {}
+                    }
+
+                    #[allow(unused)]
+                    fn d() {}
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                mod demo {
+                    #[autodefault(config(except(Bar), typed))]
+                    fn a() {}
+
+                    #[autodefault::autodefault(config(except(Bar), typed), only(Baz))]
+                    fn b() {}
+
+                    impl Foo {
+                        #[autodefault(config(except(Bar), typed))]
+                        fn c() {}
+                    }
+
+                    #[autodefault(config(except(Bar), typed))]
+                    trait Qux {
+                        #[autodefault(config(except(Bar), typed))]
+                        fn e() {}
+                    }
+
+                    #[allow(unused)]
+                    fn d() {}
+                }
+            }
+            .to_string()
+        );
+
+        let output = config_impl(
+            quote! { use(no_default) },
+            quote! {
+                mod demo {
+                    #[autodefault]
+                    fn a() {}
+                }
+            },
+        )
+        .to_string();
+        assert!(output.contains("'use' can't be given in #[autodefault_config]"));
+        assert!(!output.contains("config ("), "{}", output);
+
+        let output = autodefault_impl(
+            quote! { config(use(no_default)) },
+            quote! {
+                fn demo() {}
+            },
+        )
+        .to_string();
+        assert!(output.contains("'use' can't be given in config"));
+
+        let output = autodefault_impl(
+            quote! { config(except(Bar), typed), only(Bar) },
+            quote! {
+                fn demo() {
+                    let a = Foo {};
+                    let b = Bar {};
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = Foo {};
+                    let b = Bar { ..<Bar as ::core::default::Default>::default() };
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn define_filters() {
+        let output = define_filters_impl(quote! { no_default = except(Foo, Bar); typed = typed });
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                #[allow(unused_macros)]
+                macro_rules! no_default {
+                    (($($args:tt)*) $($item:tt)*) => {
+                        #[::autodefault::autodefault(config(except(Foo, Bar)), $($args)*)]
+                        $($item)*
+                    };
+                }
+
+                #[allow(unused_macros)]
+                macro_rules! typed {
+                    (($($args:tt)*) $($item:tt)*) => {
+                        #[::autodefault::autodefault(config(typed), $($args)*)]
+                        $($item)*
+                    };
+                }
+            }
+            .to_string()
+        );
+
+        let output = define_filters_impl(quote! { broken = exept(Foo) });
+        assert!(output.to_string().contains("compile_error"));
+    }
+
+    #[test]
+    fn config_and_use() {
+        // `#[autodefault(use(no_handles))]` in a module with
+        // `#[autodefault_config(except(NoDefault))]` expands to this
+        let output = autodefault_impl(
+            quote! { config(except(RawHandle)), config(except(NoDefault)) },
+            quote! {
+                fn demo() {
+                    let a = RawHandle { fd: 3 };
+                    let b = NoDefault { x: 1 };
+                    let c = Point { x: 1 };
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = RawHandle { fd: 3 };
+                    let b = NoDefault { x: 1 };
+                    let c = Point { x: 1, ..::core::default::Default::default() };
+                }
+            }
+            .to_string()
+        );
+
+        // Scalar options from a later config override earlier ones, and the
+        // function's own rules still replace the inherited ones
+        let output = autodefault_impl(
+            quote! { config(typed, path = std), config(path = core), only(Point) },
+            quote! {
+                fn demo() {
+                    let a = RawHandle { fd: 3 };
+                    let c = Point { x: 1 };
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = RawHandle { fd: 3 };
+                    let c = Point { x: 1, ..<Point as ::core::default::Default>::default() };
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn use_filters() {
+        let output = autodefault_impl(
+            quote! { typed, use(no_default), only_derive },
+            quote! {
+                fn demo() {
+                    let a = Foo {};
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                no_default! {
+                    (typed, only_derive)
+                    fn demo() {
+                        let a = Foo {};
+                    }
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn other_attributes() {
+        let output = autodefault_impl(
+            TokenStream2::new(),
+            quote! {
+                #[tokio::test]
+                #[inline]
+                async fn demo() {
+                    let a = Foo {};
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                #[tokio::test]
+                #[inline]
+                async fn demo() {
+                    let a = Foo { ..::core::default::Default::default() };
+                }
+            }
+            .to_string()
+        );
+
+        // What another attribute macro above #[autodefault] might produce
+        let output = autodefault_impl(
+            TokenStream2::new(),
+            quote! {
+                const DEMO: fn() = || {
+                    let a = Foo {};
+                };
+            },
+        );
+
+        assert!(output.to_string().contains("put it closest to the item"));
+    }
+
+    #[test]
+    fn skip() {
+        let output = autodefault_impl(
+            TokenStream2::new(),
+            quote! {
+                mod demo {
+                    #[autodefault::skip]
+                    struct SkippedByAttribute {
+                        a: i32,
+                    }
+
+                    fn demo() {
+                        #[skip]
+                        struct LocalSkipped;
+
+                        let a = SkippedByAttribute { a: 10 };
+                        let b = LocalSkipped { a: 10 };
+                        let c = Foo {};
+                    }
+
+                    fn other() {
+                        let b = LocalSkipped { a: 10 };
+                    }
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                mod demo {
+                    #[autodefault::skip]
+                    struct SkippedByAttribute {
+                        a: i32,
+                    }
+
+                    fn demo() {
+                        #[skip]
+                        struct LocalSkipped;
+
+                        let a = SkippedByAttribute { a: 10 };
+                        let b = LocalSkipped { a: 10 };
+                        let c = Foo {..::core::default::Default::default()};
+                    }
+
+                    fn other() {
+                        let b = LocalSkipped { a: 10, ..::core::default::Default::default() };
+                    }
+                }
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn skip_attribute() {
+        let skip_output = skip_impl(
+            TokenStream2::new(),
+            quote! {
+                struct SkippedByAttribute {
+                    a: i32,
+                }
+            },
+        );
+
+        assert_eq!(
+            skip_output.to_string(),
+            quote! {
+                struct SkippedByAttribute {
+                    a: i32,
+                }
+            }
+            .to_string()
+        );
+
+        // Nothing is recorded across invocations
+        let output = autodefault_impl(
+            TokenStream2::new(),
+            quote! {
+                fn demo() {
+                    let a = SkippedByAttribute { a: 10 };
+                }
+            },
+        );
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo() {
+                    let a = SkippedByAttribute { a: 10, ..::core::default::Default::default() };
+                }
+            }
+            .to_string()
+        )
+    }
+
+    #[test]
+    fn inner_item() {
+        let input = quote! {
+            fn demo () {
+                let x = Foo {a: 10, b: 10};
+
+                fn inner () {
+                    let x = Foo {a: 10, b: 10};
+                }
+            }
+        };
+        let output: TokenStream2 = autodefault_impl(TokenStream2::new(), input);
+
+        assert_eq!(
+            output.to_string(),
+            quote! {
+                fn demo () {
+                    let x = Foo {a: 10, b: 10, ..::core::default::Default::default()};
+
+                    fn inner () {
+                        let x = Foo {a: 10, b: 10};
+                    }
+                }
+            }
+            .to_string(),
         )
     }
 }